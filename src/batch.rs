@@ -0,0 +1,385 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::ConvertOptions;
+use crate::convert_file::convert_in_current_pool;
+
+/// The outcome of a batch conversion.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// The converted files, with the directory of their output.
+    pub converted: Vec<(PathBuf, PathBuf)>,
+    /// The files that could not be converted, with the reason.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl BatchReport {
+    /// Returns true if every file was converted.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Converted {} of {} file(s)",
+            self.converted.len(),
+            self.converted.len() + self.failed.len()
+        )?;
+        for (input, output) in &self.converted {
+            write!(f, "\n  ok      {} -> {}", input.display(), output.display())?;
+        }
+        for (input, error) in &self.failed {
+            write!(f, "\n  failed  {}: {error}", input.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Expands the given inputs into the list of E57 files to convert: files are
+/// kept as is, directories are replaced by the `.e57` files they contain, and
+/// glob patterns (`*` and `?` in any path component) by the files they match.
+/// Duplicates are removed, keeping the first occurrence.
+///
+/// # Example
+/// ```no_run
+/// let inputs = [String::from("deliveries/*/scans"), String::from("extra.e57")];
+/// let files = e57_to_las::expand_inputs(&inputs).expect("Failed to list inputs");
+/// ```
+pub fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let paths = if is_pattern(input) {
+            let matches = glob(Path::new(input))?;
+            if matches.is_empty() {
+                return Err(anyhow::anyhow!("No file matches {input}"));
+            }
+            matches
+        } else {
+            vec![PathBuf::from(input)]
+        };
+
+        for path in paths {
+            if path.is_dir() {
+                files.extend(e57_files(&path)?);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut unique = Vec::with_capacity(files.len());
+    for file in files {
+        if !unique.contains(&file) {
+            unique.push(file);
+        }
+    }
+    Ok(unique)
+}
+
+/// Converts several e57 files with the same [`ConvertOptions`], each into
+/// `<output_path>/<stem>/` (e.g. `<output_path>/<stem>/las/0.las`). Files
+/// sharing a stem get a `_<n>` suffix. All the files are converted one after
+/// the other in a single rayon thread pool of `options.number_of_threads`.
+///
+/// A file that fails does not stop the batch: the returned [`BatchReport`]
+/// lists the files converted and the ones that failed.
+///
+/// # Example
+/// ```no_run
+/// use e57_to_las::{convert_files_with_options, expand_inputs, ConvertOptions};
+///
+/// let inputs = expand_inputs(&[String::from("path/to/project")]).expect("Failed to list inputs");
+/// let report = convert_files_with_options(&inputs, "path/to/output", &ConvertOptions::default())
+///     .expect("Failed to start the conversion");
+/// println!("{report}");
+/// ```
+pub fn convert_files_with_options(
+    input_paths: &[PathBuf],
+    output_path: impl AsRef<Path>,
+    options: &ConvertOptions,
+) -> Result<BatchReport> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.number_of_threads)
+        .build()
+        .context("Failed to initialize the thread pool for rayon")?;
+
+    let output_dirs = output_dirs(input_paths, output_path.as_ref());
+    let mut report = BatchReport::default();
+
+    pool.install(|| {
+        for (index, (input_path, output_dir)) in input_paths.iter().zip(output_dirs).enumerate() {
+            println!(
+                "Converting file {}/{}: {}...",
+                index + 1,
+                input_paths.len(),
+                input_path.display()
+            );
+
+            let result = convert_in_current_pool(
+                &input_path.to_string_lossy(),
+                &output_dir.to_string_lossy(),
+                options,
+            );
+            match result {
+                Ok(()) => report.converted.push((input_path.clone(), output_dir)),
+                Err(error) => {
+                    eprintln!(
+                        "Error: failed to convert {}: {error:#}",
+                        input_path.display()
+                    );
+                    report
+                        .failed
+                        .push((input_path.clone(), format!("{error:#}")));
+                }
+            }
+        }
+    });
+
+    Ok(report)
+}
+
+/// Returns the output directory of every input: `<output_path>/<stem>`, with
+/// a `_2`, `_3`... suffix for the names already taken, regardless of case.
+fn output_dirs(input_paths: &[PathBuf], output_path: &Path) -> Vec<PathBuf> {
+    let mut taken = HashSet::new();
+    input_paths
+        .iter()
+        .map(|input_path| {
+            let stem: String = input_path
+                .file_stem()
+                .map_or_else(|| String::from("e57"), |stem| stem.to_string_lossy().into());
+
+            let mut name = stem.clone();
+            let mut count = 1;
+            while !taken.insert(name.to_lowercase()) {
+                count += 1;
+                name = format!("{stem}_{count}");
+            }
+            output_path.join(name)
+        })
+        .collect()
+}
+
+/// Lists the `.e57` files of a directory, sorted by name.
+fn e57_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = std::fs::read_dir(dir)
+        .with_context(|| format!("Unable to read directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("e57"))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+fn is_pattern(value: &str) -> bool {
+    value.contains(['*', '?'])
+}
+
+/// Returns the paths matching a pattern whose components may hold `*` and
+/// `?` wildcards, sorted by name.
+fn glob(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let Component::Normal(name) = component else {
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        };
+
+        let name = name.to_string_lossy();
+        if !is_pattern(&name) {
+            for path in &mut paths {
+                path.push(name.as_ref());
+            }
+            continue;
+        }
+
+        let mut matches = Vec::new();
+        for path in &paths {
+            let dir = if path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                path.as_path()
+            };
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let file_name = entry.file_name();
+                if wildcard_match(&name, &file_name.to_string_lossy()) {
+                    matches.push(path.join(file_name));
+                }
+            }
+        }
+        matches.sort();
+        paths = matches;
+    }
+
+    Ok(paths.into_iter().filter(|path| path.exists()).collect())
+}
+
+/// Matches a name against a pattern where `*` stands for any sequence of
+/// characters and `?` for any single character.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cartesian_prototype, write_e57};
+    use e57::RecordValue;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.e57", "scan.e57"));
+        assert!(wildcard_match("scan_??.e57", "scan_01.e57"));
+        assert!(wildcard_match("*_*.e57", "site_a_01.e57"));
+        assert!(!wildcard_match("*.e57", "scan.las"));
+        assert!(!wildcard_match("scan_?.e57", "scan_01.e57"));
+    }
+
+    #[test]
+    fn test_output_dirs() {
+        let inputs = [
+            PathBuf::from("a/scan.e57"),
+            PathBuf::from("b/scan.e57"),
+            PathBuf::from("b/other.e57"),
+        ];
+        assert_eq!(
+            output_dirs(&inputs, Path::new("out")),
+            [
+                PathBuf::from("out/scan"),
+                PathBuf::from("out/scan_2"),
+                PathBuf::from("out/other"),
+            ]
+        );
+
+        // A suffixed name may collide with a real stem, and stems differing
+        // only by case collide on case-insensitive file systems.
+        let inputs = [
+            PathBuf::from("a/scan.e57"),
+            PathBuf::from("b/scan.e57"),
+            PathBuf::from("c/scan_2.e57"),
+            PathBuf::from("d/SCAN.e57"),
+        ];
+        assert_eq!(
+            output_dirs(&inputs, Path::new("out")),
+            [
+                PathBuf::from("out/scan"),
+                PathBuf::from("out/scan_2"),
+                PathBuf::from("out/scan_2_2"),
+                PathBuf::from("out/SCAN_3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_inputs() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        for site in ["site_a", "site_b"] {
+            std::fs::create_dir(dir.path().join(site)).expect("Failed to create dir");
+            for name in ["1.e57", "2.E57", "notes.txt"] {
+                std::fs::write(dir.path().join(site).join(name), b"").expect("Failed to write");
+            }
+        }
+        let root = dir.path().to_string_lossy();
+
+        let files = expand_inputs(&[format!("{root}/site_a")]).expect("Failed to expand");
+        assert_eq!(
+            files,
+            [
+                dir.path().join("site_a").join("1.e57"),
+                dir.path().join("site_a").join("2.E57"),
+            ]
+        );
+
+        let files = expand_inputs(&[
+            format!("{root}/site_*/1.e57"),
+            format!("{root}/site_a/1.e57"),
+        ])
+        .expect("Failed to expand");
+        assert_eq!(
+            files,
+            [
+                dir.path().join("site_a").join("1.e57"),
+                dir.path().join("site_b").join("1.e57"),
+            ]
+        );
+
+        assert!(expand_inputs(&[format!("{root}/*.las")]).is_err());
+    }
+
+    #[test]
+    fn test_convert_files() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let inputs = [
+            dir.path().join("first.e57"),
+            dir.path().join("second.e57"),
+            dir.path().join("missing.e57"),
+        ];
+        for input in &inputs[..2] {
+            write_e57(
+                input,
+                cartesian_prototype(),
+                vec![
+                    vec![RecordValue::Double(1.0); 3],
+                    vec![RecordValue::Double(2.0); 3],
+                ],
+            );
+        }
+
+        let output = dir.path().join("out");
+        let report = convert_files_with_options(&inputs, &output, &ConvertOptions::default())
+            .expect("Failed to convert files");
+
+        assert!(!report.is_success());
+        assert_eq!(report.converted.len(), 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, inputs[2]);
+        assert!(output.join("first").join("las").join("0.las").is_file());
+        assert!(output.join("second").join("las").join("0.las").is_file());
+        assert!(report.to_string().starts_with("Converted 2 of 3 file(s)"));
+    }
+}
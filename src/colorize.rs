@@ -0,0 +1,334 @@
+use std::io::{Read, Seek};
+
+use anyhow::{Context, Result, anyhow};
+use e57::{Blob, E57Reader, Image, ImageFormat, PointCloud, Projection, RecordName};
+use image::{GrayImage, RgbImage};
+
+use crate::pose::RigidTransform;
+
+/// How the points are projected into an image, in the frame of the image.
+#[derive(Debug, Clone, Copy)]
+enum Model {
+    /// Azimuth from the x axis towards the y axis and elevation from the xy
+    /// plane towards z, both in radians; the center of the image is at azimuth
+    /// and elevation 0, the azimuth decreasing from left to right (as seen from
+    /// the center of projection) and the elevation from top to bottom.
+    Spherical { pixel_width: f64, pixel_height: f64 },
+    /// A camera looking along the z axis, with the x axis towards the right of
+    /// the image and the y axis towards its bottom; lengths in meters.
+    Pinhole {
+        focal_length: f64,
+        pixel_width: f64,
+        pixel_height: f64,
+        principal_x: f64,
+        principal_y: f64,
+    },
+}
+
+/// A decoded image with its projection model.
+struct ProjectedImage {
+    pixels: RgbImage,
+    /// Pixels set to zero are not valid, e.g. outside of a fisheye lens.
+    mask: Option<GrayImage>,
+    model: Model,
+    /// Transform from the project frame to the frame of the image.
+    to_image: RigidTransform,
+}
+
+impl ProjectedImage {
+    /// Returns the color of the pixel a point of the project frame is
+    /// projected on, if it is in the image.
+    fn color(&self, position: [f64; 3]) -> Option<las::Color> {
+        let [x, y, z] = self.to_image.apply(position);
+        let (width, height) = self.pixels.dimensions();
+
+        let (column, row) = match self.model {
+            Model::Spherical {
+                pixel_width,
+                pixel_height,
+            } => {
+                let azimuth = y.atan2(x);
+                let elevation = z.atan2(x.hypot(y));
+                (
+                    f64::from(width) / 2.0 - azimuth / pixel_width,
+                    f64::from(height) / 2.0 - elevation / pixel_height,
+                )
+            }
+            Model::Pinhole {
+                focal_length,
+                pixel_width,
+                pixel_height,
+                principal_x,
+                principal_y,
+            } => {
+                if z <= 0.0 {
+                    return None;
+                }
+                (
+                    principal_x + focal_length * x / (z * pixel_width),
+                    principal_y + focal_length * y / (z * pixel_height),
+                )
+            }
+        };
+
+        let (column, row) = (column.floor(), row.floor());
+        if !(0.0..f64::from(width)).contains(&column) || !(0.0..f64::from(height)).contains(&row) {
+            return None;
+        }
+        let (column, row) = (column as u32, row as u32);
+
+        if let Some(mask) = &self.mask
+            && mask
+                .get_pixel_checked(column, row)
+                .is_none_or(|value| value.0[0] == 0)
+        {
+            return None;
+        }
+
+        let [red, green, blue] = self.pixels.get_pixel(column, row).0;
+        Some(las::Color {
+            red: u16::from(red) * 257,
+            green: u16::from(green) * 257,
+            blue: u16::from(blue) * 257,
+        })
+    }
+}
+
+/// Colorizes the points of an uncolored point cloud from the spherical and
+/// pinhole `images2D` taken with it.
+pub(crate) struct Colorizer {
+    images: Vec<ProjectedImage>,
+}
+
+impl Colorizer {
+    /// Decodes the spherical and pinhole images of `pointcloud`, if it has no
+    /// color of its own; returns `None` if it has or if it has no such image.
+    /// Spherical images come first, since they usually cover the whole scan.
+    pub(crate) fn new<R: Read + Seek>(
+        e57_reader: &mut E57Reader<R>,
+        pointcloud: &PointCloud,
+    ) -> Result<Option<Self>> {
+        let has_color = pointcloud
+            .prototype
+            .iter()
+            .any(|record| record.name == RecordName::ColorRed);
+        if has_color || pointcloud.guid.is_none() {
+            return Ok(None);
+        }
+
+        let mut images = e57_reader
+            .images()
+            .into_iter()
+            .filter(|image| {
+                image.pointcloud_guid.is_some() && image.pointcloud_guid == pointcloud.guid
+            })
+            .collect::<Vec<Image>>();
+        images.sort_by_key(|image| !matches!(image.projection, Some(Projection::Spherical(_))));
+
+        let mut projected = Vec::new();
+        for image in &images {
+            let (blob, format, mask, model) = match &image.projection {
+                Some(Projection::Spherical(spherical)) => (
+                    &spherical.blob.data,
+                    &spherical.blob.format,
+                    &spherical.mask,
+                    Model::Spherical {
+                        pixel_width: spherical.properties.pixel_width,
+                        pixel_height: spherical.properties.pixel_height,
+                    },
+                ),
+                Some(Projection::Pinhole(pinhole)) => (
+                    &pinhole.blob.data,
+                    &pinhole.blob.format,
+                    &pinhole.mask,
+                    Model::Pinhole {
+                        focal_length: pinhole.properties.focal_length,
+                        pixel_width: pinhole.properties.pixel_width,
+                        pixel_height: pinhole.properties.pixel_height,
+                        principal_x: pinhole.properties.principal_x,
+                        principal_y: pinhole.properties.principal_y,
+                    },
+                ),
+                // Cylindrical images and visual references are not used.
+                _ => continue,
+            };
+
+            let name = image
+                .name
+                .as_deref()
+                .or(image.guid.as_deref())
+                .unwrap_or("?");
+            let pixels = decode(e57_reader, blob, format)
+                .with_context(|| format!("Unable to decode image {name}"))?
+                .to_rgb8();
+            let mask = mask
+                .as_ref()
+                .map(|mask| decode(e57_reader, mask, &ImageFormat::Png))
+                .transpose()
+                .with_context(|| format!("Unable to decode the mask of image {name}"))?
+                .map(|mask| mask.to_luma8());
+
+            let to_image = image
+                .transform
+                .as_ref()
+                .map_or(RigidTransform::IDENTITY, |pose| {
+                    RigidTransform::from(pose).inverse()
+                });
+
+            projected.push(ProjectedImage {
+                pixels,
+                mask,
+                model,
+                to_image,
+            });
+        }
+
+        Ok((!projected.is_empty()).then_some(Self { images: projected }))
+    }
+
+    /// Returns the color of a point of the project frame (i.e. with the scan
+    /// pose applied), from the first image it is projected in.
+    pub(crate) fn color(&self, position: [f64; 3]) -> Option<las::Color> {
+        self.images.iter().find_map(|image| image.color(position))
+    }
+}
+
+/// Reads and decodes an image blob.
+fn decode<R: Read + Seek>(
+    e57_reader: &mut E57Reader<R>,
+    blob: &Blob,
+    format: &ImageFormat,
+) -> Result<image::DynamicImage> {
+    let mut data = Vec::new();
+    e57_reader.blob(blob, &mut data)?;
+
+    let format = match format {
+        ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+        ImageFormat::Png => image::ImageFormat::Png,
+    };
+    image::load_from_memory_with_format(&data, format).map_err(|error| anyhow!(error))
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{POINTCLOUD_GUID, cartesian_prototype, write_e57_with};
+    use e57::{RecordValue, SphericalImageProperties, Transform, Translation};
+    use image::Rgb;
+    use std::f64::consts::PI;
+
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+    /// A panorama whose left half (positive azimuths) is red and right half
+    /// blue.
+    fn panorama() -> RgbImage {
+        RgbImage::from_fn(8, 4, |column, _| if column < 4 { RED } else { BLUE })
+    }
+
+    fn color(rgb: Rgb<u8>) -> las::Color {
+        las::Color {
+            red: u16::from(rgb.0[0]) * 257,
+            green: u16::from(rgb.0[1]) * 257,
+            blue: u16::from(rgb.0[2]) * 257,
+        }
+    }
+
+    #[test]
+    fn test_spherical_projection() {
+        let image = ProjectedImage {
+            pixels: panorama(),
+            mask: None,
+            model: Model::Spherical {
+                pixel_width: PI / 4.0,
+                pixel_height: PI / 4.0,
+            },
+            to_image: RigidTransform::IDENTITY,
+        };
+
+        assert_eq!(image.color([1.0, 1.0, 0.0]), Some(color(RED)));
+        assert_eq!(image.color([1.0, -1.0, 0.0]), Some(color(BLUE)));
+    }
+
+    #[test]
+    fn test_pinhole_projection() {
+        let mut pixels = RgbImage::from_pixel(4, 4, BLUE);
+        pixels.put_pixel(3, 2, RED);
+        let image = ProjectedImage {
+            pixels,
+            mask: None,
+            model: Model::Pinhole {
+                focal_length: 0.01,
+                pixel_width: 0.001,
+                pixel_height: 0.001,
+                principal_x: 2.0,
+                principal_y: 2.0,
+            },
+            to_image: RigidTransform::IDENTITY,
+        };
+
+        assert_eq!(image.color([0.0, 0.0, 5.0]), Some(color(BLUE)));
+        assert_eq!(image.color([0.15, 0.05, 1.0]), Some(color(RED)));
+        // Behind the camera or outside of the image.
+        assert_eq!(image.color([0.0, 0.0, -5.0]), None);
+        assert_eq!(image.color([1.0, 0.0, 1.0]), None);
+    }
+
+    #[test]
+    fn test_colorizer_from_e57() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("colorize.e57");
+        let pose = Transform {
+            translation: Translation {
+                x: 100.0,
+                y: 200.0,
+                z: 0.0,
+            },
+            ..Default::default()
+        };
+
+        let mut png = Vec::new();
+        panorama()
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .expect("Failed to encode panorama");
+
+        write_e57_with(
+            &path,
+            cartesian_prototype(),
+            vec![vec![RecordValue::Double(1.0); 3]],
+            |pointcloud_writer| pointcloud_writer.set_transform(Some(pose.clone())),
+            |writer| {
+                let mut image_writer = writer
+                    .add_image("b0000000-0000-0000-0000-000000000001")
+                    .expect("Failed to add image");
+                image_writer.set_pointcloud_guid(POINTCLOUD_GUID);
+                image_writer.set_transform(pose.clone());
+                image_writer
+                    .add_spherical(
+                        ImageFormat::Png,
+                        &mut png.as_slice(),
+                        SphericalImageProperties {
+                            width: 8,
+                            height: 4,
+                            pixel_width: PI / 4.0,
+                            pixel_height: PI / 4.0,
+                        },
+                        None,
+                    )
+                    .expect("Failed to add spherical image");
+                image_writer.finalize().expect("Failed to finalize image");
+            },
+        );
+
+        let mut e57_reader = E57Reader::from_file(&path).expect("Failed to open E57 file");
+        let pointclouds = e57_reader.pointclouds();
+        let colorizer = Colorizer::new(&mut e57_reader, &pointclouds[0])
+            .expect("Failed to load images")
+            .expect("Expected a colorizer for the uncolored point cloud");
+
+        // Positions in the project frame, around the scanner.
+        assert_eq!(colorizer.color([101.0, 201.0, 0.0]), Some(color(RED)));
+        assert_eq!(colorizer.color([101.0, 199.0, 0.0]), Some(color(BLUE)));
+    }
+}
@@ -1,12 +1,18 @@
 use rayon::prelude::*;
-use std::path::Path;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
 
-use crate::convert_pointcloud::{convert_pointcloud, convert_pointclouds};
+use crate::convert_pointcloud::{Source, convert_pointclouds, convert_source, convert_sources};
+use crate::header_metadata::HeaderMetadata;
 
-use crate::LasVersion;
+use crate::images::save_images;
 use crate::stations::save_stations;
+use crate::{ConvertOptions, LasVersion};
 
 /// Converts a given e57 file into LAS format and, optionally, as stations.
 ///
@@ -17,9 +23,10 @@ use crate::stations::save_stations;
 /// - `input_path`: The path to the e57 file that needs to be converted.
 /// - `output_path`: The destination (output dir) where the files will be saved.
 /// - `number_of_threads`: The number of threads to be used for parallel processing.
-/// - `as_stations`: Whether to convert e57 file in distinct stations
-/// - `las_version`: Version of LAS format used for output file. Latest one is (1, 4). Currently possible: (1, 3) and (1, 4).
-/// or in single LAS file
+/// - `as_stations`: Whether to convert e57 file in distinct stations or in single LAS file.
+/// - `las_version`: Version of LAS format used for output file. Latest one is (1, 4). Currently possible: (1, 0) to (1, 4).
+///
+/// See [`convert_file_with_options`] for the other conversion options.
 ///
 /// # Example
 /// ```
@@ -38,55 +45,310 @@ pub fn convert_file(
     number_of_threads: usize,
     as_stations: bool,
     las_version: LasVersion,
+) -> Result<()> {
+    let options = ConvertOptions {
+        number_of_threads,
+        as_stations,
+        las_version,
+        ..Default::default()
+    };
+
+    convert_file_with_options(input_path, output_path, &options)
+}
+
+/// Converts a given e57 file into LAS format using the given [`ConvertOptions`].
+///
+/// With `options.as_stations`, every point cloud is written to
+/// `<output_path>/las/<index>.las`, or to the name given by
+/// `options.name_template`, and the stations files give the path of the file
+/// of every station.
+///
+/// # Parameters
+/// - `input_path`: The path to the e57 file that needs to be converted.
+/// - `output_path`: The destination (output dir) where the files will be saved.
+/// - `options`: The conversion options.
+///
+/// # Example
+/// ```
+/// use e57_to_las::{convert_file_with_options, ConvertOptions};
+///
+/// let input_path = String::from("path/to/input.e57");
+/// let output_path = String::from("path/to/output");
+/// let options = ConvertOptions {
+///     streaming: true,
+///     ..Default::default()
+/// };
+/// let _ = convert_file_with_options(input_path, output_path, &options);
+/// ```
+pub fn convert_file_with_options(
+    input_path: String,
+    output_path: String,
+    options: &ConvertOptions,
 ) -> Result<()> {
     let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(number_of_threads)
+        .num_threads(options.number_of_threads)
         .build()
         .context("Failed to initialize the thread pool for rayon")?;
 
-    pool.install(|| {
-        let e57_reader =
-            e57::E57Reader::from_file(&input_path).context("Failed to open e57 file")?;
+    pool.install(|| convert_in_current_pool(&input_path, &output_path, options))
+}
 
-        if e57_reader.format_name() != "ASTM E57 3D Imaging Data File" {
-            return Err(anyhow::anyhow!("Invalid file format"));
-        }
+/// Merges several e57 files, e.g. one per scan exported from a registration
+/// software, into a single LAS file using the given [`ConvertOptions`].
+///
+/// The point clouds of all the files are numbered one after the other, in the
+/// order of `input_paths`, and the points of each one get its number (or the
+/// ID `options.point_source_ids` maps it to) as LAS point source ID. They are
+/// written to `<output_path>/las/0.las`, with bounds, scale and offset computed
+/// across all the files, or, with `options.as_stations`, to one file per point
+/// cloud along with a single stations file, as [`convert_file_with_options`]
+/// does. A `crs` of [`crate::Crs::E57`] uses the coordinate metadata of the
+/// first file.
+///
+/// # Example
+/// ```no_run
+/// use std::path::PathBuf;
+/// use e57_to_las::{merge_files_with_options, ConvertOptions};
+///
+/// let inputs = [PathBuf::from("scan_01.e57"), PathBuf::from("scan_02.e57")];
+/// let _ = merge_files_with_options(&inputs, "path/to/output", &ConvertOptions::default());
+/// ```
+pub fn merge_files_with_options(
+    input_paths: &[PathBuf],
+    output_path: impl AsRef<Path>,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let output_path = output_path.as_ref();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.number_of_threads)
+        .build()
+        .context("Failed to initialize the thread pool for rayon")?;
 
-        let pointclouds = e57_reader.pointclouds();
+    let e57_readers = input_paths
+        .iter()
+        .map(|input_path| open_e57(input_path))
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = e57_readers.first() else {
+        return Err(anyhow::anyhow!("No e57 file to merge"));
+    };
+    let options = &checked_options(options, first.coordinate_metadata())?;
+
+    if options.images {
+        return Err(anyhow::anyhow!(
+            "Image export cannot be combined with the merge of several files"
+        ));
+    }
 
-        if as_stations {
+    let pointclouds = e57_readers
+        .iter()
+        .map(|e57_reader| e57_reader.pointclouds())
+        .collect::<Vec<_>>();
+    let inputs = input_paths
+        .iter()
+        .zip(&pointclouds)
+        .flat_map(|(input_path, pointclouds)| {
             pointclouds
+                .iter()
+                .map(move |pointcloud| (input_path.as_path(), pointcloud))
+        })
+        .collect::<Vec<_>>();
+    let pointcloud_refs = inputs
+        .iter()
+        .map(|(_, pointcloud)| *pointcloud)
+        .collect::<Vec<_>>();
+    let source_ids = options.point_source_ids.resolve(&pointcloud_refs)?;
+    let sources = inputs
+        .iter()
+        .zip(&source_ids)
+        .map(|((input_path, pointcloud), source_id)| Source {
+            input_path,
+            pointcloud,
+            source_id: *source_id,
+        })
+        .collect::<Vec<_>>();
+
+    let files = e57_readers
+        .iter()
+        .zip(&pointclouds)
+        .map(|(e57_reader, pointclouds)| (e57_reader, pointclouds.iter().collect()))
+        .collect::<Vec<_>>();
+    let mut metadata = HeaderMetadata::merged(&files)?;
+    metadata.set_point_sources(&pointcloud_refs, &source_ids)?;
+    drop(files);
+    drop(e57_readers);
+
+    let all_pointclouds = pointclouds.concat();
+    let file_names = options.as_stations.then(|| {
+        let file_stems = input_paths
+            .iter()
+            .map(|path| file_stem(path))
+            .collect::<Vec<_>>();
+        let scans = file_stems
+            .iter()
+            .zip(&pointclouds)
+            .flat_map(|(file_stem, pointclouds)| {
+                pointclouds
+                    .iter()
+                    .map(move |pointcloud| (file_stem.as_str(), pointcloud))
+            })
+            .collect::<Vec<_>>();
+        options
+            .name_template
+            .file_names(&scans, options.output_extension())
+    });
+
+    pool.install(|| {
+        if let Some(file_names) = &file_names {
+            sources
                 .par_iter()
+                .zip(file_names)
                 .enumerate()
-                .try_for_each(|(index, pointcloud)| -> Result<()> {
+                .try_for_each(|(index, (source, file_name))| -> Result<()> {
                     println!("Saving pointcloud {index}...");
 
-                    convert_pointcloud(
-                        index,
-                        pointcloud,
-                        Path::new(&input_path),
-                        Path::new(&output_path),
-                        &las_version,
-                    )
-                    .context(format!("Error while converting pointcloud {}", index))?;
-
-                    Ok(())
+                    convert_source(index, *source, file_name, output_path, options)
+                        .context(format!("Error while converting pointcloud {index}"))
                 })
                 .context("Error during the parallel processing of pointclouds")?;
-
-            save_stations(output_path, &pointclouds)?;
         } else {
-            convert_pointclouds(
-                Path::new(&input_path),
-                Path::new(&output_path),
-                &las_version,
-            )
-            .context("Error during the parallel processing of pointclouds")?;
+            let name = output_path.file_name().map_or_else(
+                || String::from("merged"),
+                |name| name.to_string_lossy().into(),
+            );
+            convert_sources(&sources, &metadata, name, output_path, options)
+                .context("Error during the parallel processing of pointclouds")?;
+        }
+
+        if options.as_stations || options.potree {
+            save_stations(
+                output_path,
+                &all_pointclouds,
+                file_names.as_deref(),
+                options,
+            )?;
         }
+
         Ok(())
     })
 }
 
+/// Returns the stem of the name of an e57 file, for the name templates.
+fn file_stem(input_path: &Path) -> String {
+    input_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into()
+}
+
+/// Opens an e57 file, checking its format name.
+fn open_e57(input_path: &Path) -> Result<e57::E57Reader<BufReader<File>>> {
+    let e57_reader = e57::E57Reader::from_file(input_path)
+        .with_context(|| format!("Failed to open e57 file {}", input_path.display()))?;
+
+    if e57_reader.format_name() != "ASTM E57 3D Imaging Data File" {
+        return Err(anyhow::anyhow!("Invalid file format"));
+    }
+
+    Ok(e57_reader)
+}
+
+/// Resolves the CRS of `options` against the coordinate metadata of the E57
+/// file and checks that the options can be combined.
+fn checked_options(
+    options: &ConvertOptions,
+    coordinate_metadata: Option<&str>,
+) -> Result<ConvertOptions> {
+    let options = ConvertOptions {
+        crs: options
+            .crs
+            .as_ref()
+            .and_then(|crs| crs.resolve(coordinate_metadata)),
+        ..options.clone()
+    };
+
+    options
+        .point_format
+        .check(las::Version::from(&options.las_version), options.copc)?;
+
+    if options.colorize && !cfg!(feature = "colorize") {
+        return Err(anyhow::anyhow!(
+            "Colorization requires e57-to-las to be built with the `colorize` feature"
+        ));
+    }
+
+    if options.as_stations && options.potree {
+        return Err(anyhow::anyhow!(
+            "Potree output cannot be combined with the conversion in distinct stations"
+        ));
+    }
+
+    Ok(options)
+}
+
+/// Converts a given e57 file like [`convert_file_with_options`], in the rayon
+/// thread pool of the caller, so that several files can share one pool.
+pub(crate) fn convert_in_current_pool(
+    input_path: &str,
+    output_path: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let e57_reader = open_e57(Path::new(input_path))?;
+    let pointclouds = e57_reader.pointclouds();
+    let options = &checked_options(options, e57_reader.coordinate_metadata())?;
+
+    if options.as_stations {
+        let file_stem = file_stem(Path::new(input_path));
+        let scans = pointclouds
+            .iter()
+            .map(|pointcloud| (file_stem.as_str(), pointcloud))
+            .collect::<Vec<_>>();
+        let file_names = options
+            .name_template
+            .file_names(&scans, options.output_extension());
+
+        pointclouds
+            .par_iter()
+            .zip(&file_names)
+            .enumerate()
+            .try_for_each(|(index, (pointcloud, file_name))| -> Result<()> {
+                println!("Saving pointcloud {index}...");
+
+                let source = Source {
+                    input_path: Path::new(&input_path),
+                    pointcloud,
+                    source_id: 0,
+                };
+                convert_source(index, source, file_name, Path::new(&output_path), options)
+                    .context(format!("Error while converting pointcloud {}", index))?;
+
+                Ok(())
+            })
+            .context("Error during the parallel processing of pointclouds")?;
+
+        save_stations(output_path, &pointclouds, Some(&file_names), options)?;
+    } else {
+        convert_pointclouds(Path::new(&input_path), Path::new(&output_path), options)
+            .context("Error during the parallel processing of pointclouds")?;
+
+        if options.potree {
+            save_stations(output_path, &pointclouds, None, options)?;
+        }
+    }
+
+    if options.images {
+        save_images(
+            Path::new(&input_path),
+            Path::new(&output_path),
+            &pointclouds,
+            options,
+        )
+        .context("Error while exporting the images")?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 #[allow(clippy::panic, clippy::expect_used)]
 mod tests {
@@ -212,4 +474,138 @@ mod tests {
 
         let _ = std::fs::remove_dir_all(&output_dir);
     }
+
+    /// Returns the path of the bunny test fixture, panicking with a helpful
+    /// message when it is missing or still a Git LFS pointer.
+    fn bunny_fixture() -> String {
+        let input_path = String::from("examples/bunnyDouble.e57");
+        if !Path::new(&input_path).is_file() {
+            panic!(
+                "Missing test fixture '{}'. Run `git lfs pull` to retrieve large test files.",
+                input_path
+            );
+        }
+
+        let is_lfs_pointer = std::fs::read_to_string(&input_path)
+            .map(|content| content.starts_with("version https://git-lfs.github.com/spec/v1\n"))
+            .unwrap_or(false);
+
+        if is_lfs_pointer {
+            panic!(
+                "Test fixture '{}' is a Git LFS pointer. Run `git lfs pull` to retrieve large test files.",
+                input_path
+            );
+        }
+
+        input_path
+    }
+
+    #[test]
+    fn test_streaming_matches_in_memory_conversion() {
+        let input_path = bunny_fixture();
+        let output_dir = tempfile::tempdir().expect("Failed to create temp output dir");
+        let in_memory_dir = output_dir.path().join("in_memory");
+        let streaming_dir = output_dir.path().join("streaming");
+
+        let in_memory = convert_file_with_options(
+            input_path.clone(),
+            in_memory_dir.to_string_lossy().into_owned(),
+            &ConvertOptions::default(),
+        );
+        assert!(
+            in_memory.is_ok(),
+            "in-memory conversion failed: {:?}",
+            in_memory
+        );
+
+        let streaming = convert_file_with_options(
+            input_path,
+            streaming_dir.to_string_lossy().into_owned(),
+            &ConvertOptions {
+                streaming: true,
+                buffer_size: 1000,
+                ..Default::default()
+            },
+        );
+        assert!(
+            streaming.is_ok(),
+            "streaming conversion failed: {:?}",
+            streaming
+        );
+
+        let in_memory_reader = las::Reader::from_path(in_memory_dir.join("las").join("0.las"))
+            .expect("Failed to read in-memory output");
+        let streaming_reader = las::Reader::from_path(streaming_dir.join("las").join("0.las"))
+            .expect("Failed to read streaming output");
+        assert_eq!(
+            in_memory_reader.header().number_of_points(),
+            streaming_reader.header().number_of_points()
+        );
+        assert_eq!(
+            in_memory_reader.header().transforms(),
+            streaming_reader.header().transforms()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "laz")]
+    fn test_convert_bunny_compressed() {
+        let input_path = bunny_fixture();
+        let output_dir = tempfile::tempdir().expect("Failed to create temp output dir");
+
+        let result = convert_file_with_options(
+            input_path,
+            output_dir.path().to_string_lossy().into_owned(),
+            &ConvertOptions {
+                compress: true,
+                ..Default::default()
+            },
+        );
+        assert!(result.is_ok(), "compressed conversion failed: {:?}", result);
+
+        let reader = las::Reader::from_path(output_dir.path().join("las").join("0.laz"))
+            .expect("Failed to read compressed output");
+        assert!(reader.header().point_format().is_compressed);
+        assert!(reader.header().number_of_points() > 0);
+    }
+
+    #[test]
+    fn test_merge_files() {
+        let input_path = PathBuf::from(bunny_fixture());
+        let scans = e57::E57Reader::from_file(&input_path)
+            .expect("Failed to open fixture")
+            .pointclouds()
+            .len();
+
+        let single_dir = tempfile::tempdir().expect("Failed to create temp output dir");
+        convert_file_with_options(
+            input_path.to_string_lossy().into_owned(),
+            single_dir.path().to_string_lossy().into_owned(),
+            &ConvertOptions::default(),
+        )
+        .expect("Failed to convert fixture");
+        let single = las::Reader::from_path(single_dir.path().join("las").join("0.las"))
+            .expect("Failed to read single output")
+            .header()
+            .number_of_points();
+
+        let merged_dir = tempfile::tempdir().expect("Failed to create temp output dir");
+        merge_files_with_options(
+            &[input_path.clone(), input_path],
+            merged_dir.path(),
+            &ConvertOptions::default(),
+        )
+        .expect("Failed to merge files");
+
+        let mut reader = las::Reader::from_path(merged_dir.path().join("las").join("0.las"))
+            .expect("Failed to read merged output");
+        assert_eq!(reader.header().number_of_points(), 2 * single);
+
+        let mut source_ids = reader
+            .points()
+            .map(|point| point.expect("Failed to read point").point_source_id)
+            .collect::<Vec<_>>();
+        source_ids.dedup();
+        assert_eq!(source_ids, (0..2 * scans as u16).collect::<Vec<_>>());
+    }
 }
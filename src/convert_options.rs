@@ -0,0 +1,139 @@
+use crate::{
+    Crs, DirectionPolicy, IntensityMode, InvalidPoints, LasVersion, NameTemplate, Offset,
+    PointFormat, PointSourceIds, Pose, RigidTransform, Scale, StationsFormat,
+};
+
+/// Default number of points buffered in memory before being flushed to the
+/// LAS writer in streaming mode.
+pub const DEFAULT_BUFFER_SIZE: usize = 1_000_000;
+
+/// Options controlling how an E57 file is converted to LAS.
+///
+/// # Example
+/// ```
+/// use e57_to_las::{ConvertOptions, LasVersion};
+///
+/// let options = ConvertOptions {
+///     as_stations: true,
+///     las_version: LasVersion::new(1, 2).expect("Failed to create LAS version"),
+///     streaming: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// The number of threads to be used for parallel processing (0 = max possible).
+    pub number_of_threads: usize,
+    /// Whether to convert the e57 file in distinct stations or in a single LAS file.
+    pub as_stations: bool,
+    /// The formats of the stations files written with `as_stations` or
+    /// `potree`.
+    pub stations_formats: Vec<StationsFormat>,
+    /// Version of LAS format used for the output files.
+    pub las_version: LasVersion,
+    /// LAS point data record format of the output files, checked against
+    /// `las_version` (or COPC).
+    pub point_format: PointFormat,
+    /// Whether to convert in two passes (bounds first, then points) instead of
+    /// buffering every converted point in memory before writing.
+    pub streaming: bool,
+    /// Maximum number of points held in memory at once while streaming.
+    pub buffer_size: usize,
+    /// Whether to write LAZ compressed `.laz` files instead of `.las` files.
+    /// Requires the `laz` feature.
+    pub compress: bool,
+    /// Whether to write COPC (Cloud Optimized Point Cloud) `.copc.laz` files
+    /// instead of `.las` files. COPC files always use LAS 1.4 with point format
+    /// 6 or 7, and need every point in memory to build their octree, so this
+    /// takes precedence over `las_version`, `compress` and `streaming`.
+    /// Requires the `copc` feature.
+    pub copc: bool,
+    /// Whether to write a Potree 2.0 octree (`metadata.json`, `hierarchy.bin`
+    /// and `octree.bin` in `<output>/potree/`) instead of a merged LAS file.
+    /// Like COPC, it needs every point in memory and ignores `streaming`; it
+    /// cannot be combined with `as_stations`.
+    pub potree: bool,
+    /// Coordinate reference system written in the header of the output files,
+    /// if any.
+    pub crs: Option<Crs>,
+    /// How the LAS scale of the x, y and z axes is chosen.
+    pub scale: [Scale; 3],
+    /// How the LAS offset of the x, y and z axes is chosen.
+    pub offset: [Offset; 3],
+    /// Whether to offset the E57 timestamps by the `acquisitionStart` date of
+    /// their point cloud, so that the LAS GPS times are adjusted standard GPS
+    /// times instead of raw E57 timestamps. Fails for point clouds with
+    /// timestamps but no acquisition start date.
+    pub adjusted_gps_time: bool,
+    /// How the E57 intensities are mapped to LAS intensities.
+    pub intensity: IntensityMode,
+    /// Whether the points are converted in the project frame (applying the pose
+    /// of their scan) or in the local frame of their scanner.
+    pub pose: Pose,
+    /// A rigid transform applied to every point (and station position) after
+    /// the pose, before the bounds, scale and offset are computed, e.g. to go
+    /// from a local grid to UTM coordinates.
+    pub transform: Option<RigidTransform>,
+    /// What to do with the points that only have a direction (no range).
+    pub direction_points: DirectionPolicy,
+    /// What to do with the points without valid coordinates or color.
+    pub invalid_points: InvalidPoints,
+    /// Whether to export the E57 `images2D` (panoramas and photos) to
+    /// `<output>/images/`, each with a JSON sidecar holding its pose,
+    /// projection model and scan GUID.
+    pub images: bool,
+    /// Whether to color the points of the point clouds without color from the
+    /// spherical and pinhole `images2D` taken with them. Requires the
+    /// `colorize` feature.
+    pub colorize: bool,
+    /// How the LAS point source ID of the points of each point cloud is chosen
+    /// in merged outputs. The IDs are described by a JSON VLR mapping them to
+    /// the names and GUIDs of the point clouds.
+    pub point_source_ids: PointSourceIds,
+    /// The template of the names of the LAS files of the stations, with
+    /// `options.as_stations`, e.g. `{file_stem}_{index:03}_{name}`.
+    pub name_template: NameTemplate,
+}
+
+impl ConvertOptions {
+    /// Returns the extension of the output point cloud files.
+    pub fn output_extension(&self) -> &'static str {
+        if self.copc {
+            "copc.laz"
+        } else if self.compress {
+            "laz"
+        } else {
+            "las"
+        }
+    }
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            number_of_threads: 0,
+            as_stations: false,
+            stations_formats: vec![StationsFormat::Legacy],
+            las_version: LasVersion::default(),
+            point_format: PointFormat::Auto,
+            streaming: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            compress: false,
+            copc: false,
+            potree: false,
+            crs: None,
+            scale: [Scale::Auto; 3],
+            offset: [Offset::Auto; 3],
+            adjusted_gps_time: false,
+            intensity: IntensityMode::Normalize,
+            pose: Pose::Apply,
+            transform: None,
+            direction_points: DirectionPolicy::Drop,
+            invalid_points: InvalidPoints::Drop,
+            images: false,
+            colorize: false,
+            point_source_ids: PointSourceIds::Index,
+            name_template: NameTemplate::default(),
+        }
+    }
+}
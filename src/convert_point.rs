@@ -1,16 +1,17 @@
-use e57::CartesianCoordinate;
+use crate::coordinates::LocalPosition;
 
 /// Converts an e57::Point to an optional las::Point.
 ///
-/// This function takes an e57 point, extracts the Cartesian coordinates and optional color and intensity
-/// attributes, and constructs a corresponding las point.
+/// This function takes an e57 point, extracts the Cartesian coordinates (converted from the spherical
+/// ones when only those are valid) and optional color and intensity attributes, and constructs a
+/// corresponding las point.
 ///
 /// # Parameters
 /// - `point`: The e57 point that needs to be converted.
 ///
 /// # Returns
-/// - `Option<las::Point>`: An optional las point. The function returns `None` if the Cartesian coordinates
-///   are not valid. Otherwise, it returns a `Some(las::Point)` containing the converted point.
+/// - `Option<las::Point>`: An optional las point. The function returns `None` if neither the Cartesian nor
+///   the spherical coordinates are valid (e.g. direction-only points). Otherwise, it returns a `Some(las::Point)` containing the converted point.
 ///
 /// # Example
 /// ```ignore
@@ -21,7 +22,7 @@ use e57::CartesianCoordinate;
 pub fn convert_point(point: e57::Point) -> Option<las::Point> {
     let mut las_point = las::Point::default();
 
-    if let CartesianCoordinate::Valid { x, y, z } = point.cartesian {
+    if let LocalPosition::Valid([x, y, z]) = LocalPosition::of(&point) {
         las_point.x = x;
         las_point.y = y;
         las_point.z = z;
@@ -1,64 +1,351 @@
-use std::path::Path;
-
-use crate::get_las_writer::{PointBounds, get_las_writer};
-use crate::{LasVersion, convert_point::convert_point, utils::ensure_parent_dir};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::coordinates::LocalPosition;
+use crate::extra_bytes::{self, ExtraDimension, ExtraValues};
+use crate::get_las_writer::{
+    ADJUSTED_GPS_TIME_OFFSET, PointAttributes, PointBounds, get_las_writer,
+};
+use crate::header_metadata::HeaderMetadata;
+use crate::intensity::IntensityMapping;
+use crate::potree_writer::write_potree;
+use crate::raw_fields::{RawFields, RawPoint};
+use crate::stations::station_positions;
+use crate::{
+    ConvertOptions, Crs, LasVersion, Pose, RigidTransform, convert_point::convert_point,
+    utils::ensure_parent_dir,
+};
 
 use anyhow::{Context, Result};
-use e57::{E57Reader, PointCloud};
+use e57::{CartesianCoordinate, E57Reader, PointCloud};
 use rayon::prelude::*;
 
+/// Highest return number and number of returns of the legacy LAS point formats
+/// (0 to 5).
+const MAX_LEGACY_RETURNS: u8 = 7;
+/// Highest return number and number of returns of the LAS 1.4 point formats
+/// (6 to 10).
+const MAX_RETURNS: i64 = 15;
+
+/// The metadata gathered while reading a single E57 point cloud, needed to
+/// configure a LAS writer for its points.
+#[derive(Debug, Default)]
+struct CloudSummary {
+    bounds: PointBounds,
+    attributes: PointAttributes,
+    skipped_points: usize,
+    flagged_points: usize,
+}
+
+/// A point cloud to convert, along with the E57 file it is read from.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Source<'a> {
+    pub(crate) input_path: &'a Path,
+    pub(crate) pointcloud: &'a PointCloud,
+    /// The LAS point source ID given to its points.
+    pub(crate) source_id: u16,
+}
+
 /// The LAS points of a single E57 point cloud, along with the metadata
 /// needed to configure a LAS writer for them.
 struct CloudPoints {
     points: Vec<las::Point>,
-    bounds: PointBounds,
-    has_color: bool,
-    skipped_points: usize,
+    summary: CloudSummary,
 }
 
-/// Reads a single point cloud from an E57 file and converts its points to LAS points.
+/// Reads a single point cloud from an E57 file and hands every converted LAS
+/// point, tagged with the point source ID of `source`, to `visit`.
 ///
-/// Opens its own `E57Reader` on `input_path` so that callers can safely invoke it
+/// Opens its own `E57Reader` on the input path so that callers can safely invoke it
 /// from parallel workers. Tracks the per-axis bounds of the converted points
-/// (used to derive the LAS offset and scale), whether any point carries color,
-/// and how many points were skipped because of invalid coordinates.
-fn read_pointcloud(input_path: &Path, pointcloud: &PointCloud) -> Result<CloudPoints> {
+/// (used to derive the LAS offset and scale), which optional attributes (color,
+/// GPS time) the points carry, and how many points were skipped because of
+/// invalid coordinates (or direction-only ones, depending on
+/// `options.direction_points`), unless `options.invalid_points` keeps them.
+///
+/// The fields the simple point reader does not expose (e.g. timestamps) are read
+/// by a second, raw reader running in lockstep, only when the point cloud has any.
+/// Intensities are read as is and mapped to LAS intensities by `intensity`,
+/// which may span several point clouds. Spherical coordinates are converted to
+/// cartesian ones, then the scan pose is applied according to `options.pose`,
+/// followed by `options.transform`, before the bounds are updated.
+fn visit_pointcloud<F>(
+    source: Source,
+    intensity: &IntensityMapping,
+    options: &ConvertOptions,
+    mut visit: F,
+) -> Result<CloudSummary>
+where
+    F: FnMut(las::Point) -> Result<()>,
+{
+    let Source {
+        input_path,
+        pointcloud,
+        source_id,
+    } = source;
     let mut e57_reader = E57Reader::from_file(input_path).context("Failed to open e57 file: ")?;
 
-    let pointcloud_reader = e57_reader
+    #[cfg(feature = "colorize")]
+    let colorizer = if options.colorize {
+        crate::colorize::Colorizer::new(&mut e57_reader, pointcloud)
+            .context("Unable to load the images to colorize the points: ")?
+    } else {
+        None
+    };
+    #[cfg(feature = "colorize")]
+    let scan_pose = pointcloud.transform.as_ref().map(RigidTransform::from);
+
+    let mut pointcloud_reader = e57_reader
         .pointcloud_simple(pointcloud)
         .context("Unable to get point cloud iterator: ")?;
-
-    let mut points: Vec<las::Point> = Vec::new();
-    let mut bounds = PointBounds::default();
-    let mut has_color = false;
-    let mut skipped_points: usize = 0;
+    // The intensities are mapped below, and so are the grey colors derived
+    // from them for the points without color.
+    pointcloud_reader.normalize_intensity(false);
+    pointcloud_reader.intensity_to_color(false);
+    // Spherical coordinates and the pose are handled below, since the direction
+    // points must be projected in the local frame of the scanner, and the
+    // normals read from the raw records rotated like the coordinates.
+    pointcloud_reader.spherical_to_cartesian(false);
+    pointcloud_reader.apply_pose(false);
+
+    let pose = match (options.pose, &pointcloud.transform) {
+        (Pose::Apply, Some(transform)) => Some(RigidTransform::from(transform)),
+        _ => None,
+    };
+
+    let mut raw_fields = RawFields::new(&pointcloud.prototype);
+    if !options.invalid_points.keeps() {
+        raw_fields.skip_color_invalid();
+    }
+    let mut raw_e57_reader = if raw_fields.is_empty() {
+        None
+    } else {
+        Some(E57Reader::from_file(input_path).context("Failed to open e57 file: ")?)
+    };
+    let mut raw_reader = raw_e57_reader
+        .as_mut()
+        .map(|reader| reader.pointcloud_raw(pointcloud))
+        .transpose()
+        .context("Unable to get raw point cloud iterator: ")?;
+
+    let time_offset = if raw_fields.has_timestamp() {
+        gps_time_offset(pointcloud, options)?
+    } else {
+        0.0
+    };
+
+    let grey = intensity.grey(pointcloud);
+    let mut summary = CloudSummary::default();
 
     for p in pointcloud_reader {
-        let point = p.context("Could not read point: ")?;
+        let mut point = p.context("Could not read point: ")?;
+        let raw_point = match raw_reader.as_mut() {
+            Some(reader) => {
+                let values = reader
+                    .next()
+                    .context("Raw point reader ended before the point reader")?
+                    .context("Could not read raw point: ")?;
+                raw_fields.read(&values)?
+            }
+            None => RawPoint::default(),
+        };
 
         if point.color.is_some() {
-            has_color = true;
+            summary.attributes.has_color = true;
         }
 
-        let las_point = match convert_point(point) {
+        let extra_values = ExtraValues {
+            row: (point.row >= 0).then_some(point.row),
+            column: (point.column >= 0).then_some(point.column),
+            normal: raw_point
+                .normal
+                .map(|normal| rotate_normal(normal, pose.as_ref(), options.transform.as_ref())),
+        };
+        let point_intensity = point.intensity;
+
+        // The direction-only points follow `options.direction_points`, even
+        // when dropped; the points without any position are kept at the
+        // scanner position if `options.invalid_points` keeps them.
+        let local_position = LocalPosition::of(&point);
+        let (position, is_withheld, mut is_invalid) = match local_position
+            .resolve(options.direction_points)
+        {
+            Some((position, is_withheld)) => (position, is_withheld, false),
+            None if options.invalid_points.keeps() && local_position == LocalPosition::Invalid => {
+                ([0.0; 3], false, true)
+            }
+            None => {
+                summary.skipped_points += 1;
+                continue;
+            }
+        };
+        is_invalid |= raw_point.color_invalid && options.invalid_points.keeps();
+        let [x, y, z] = position;
+        point.cartesian = CartesianCoordinate::Valid { x, y, z };
+
+        let mut las_point = match convert_point(point) {
             Some(p) => p,
             None => {
-                skipped_points += 1;
+                summary.skipped_points += 1;
                 continue;
             }
         };
+        las_point.is_withheld = is_withheld;
+
+        // The points without color get a grey one from their intensity (the
+        // colorized ones are overwritten by the image colors below).
+        if let (None, Some(value)) = (las_point.color, point_intensity) {
+            let grey = grey.apply(f64::from(value));
+            las_point.color = Some(las::Color::new(grey, grey, grey));
+            summary.attributes.has_color = true;
+        }
+
+        // The images are posed in the project frame, whatever `options.pose`.
+        #[cfg(feature = "colorize")]
+        if let Some(colorizer) = &colorizer {
+            let project_position = scan_pose
+                .as_ref()
+                .map_or(position, |scan_pose| scan_pose.apply(position));
+            las_point.color = colorizer.color(project_position);
+            summary.attributes.has_color = true;
+        }
+
+        if is_invalid {
+            options.invalid_points.flag(&mut las_point);
+            summary.flagged_points += 1;
+        }
+
+        let mut position = [las_point.x, las_point.y, las_point.z];
+        for transform in [pose.as_ref(), options.transform.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            position = transform.apply(position);
+        }
+        [las_point.x, las_point.y, las_point.z] = position;
+
+        if let Some(value) = point_intensity {
+            las_point.intensity = intensity.apply(f64::from(value));
+        }
+
+        if let Some(timestamp) = raw_point.timestamp {
+            las_point.gps_time = Some(timestamp + time_offset);
+            summary.attributes.has_gps_time = true;
+        }
 
-        bounds.update(&las_point);
-        points.push(las_point);
+        // E57 return indices start at 0, LAS return numbers at 1; both are
+        // clamped to 15 here, and to 7 for the legacy point formats once the
+        // output format is known.
+        if let Some(return_index) = raw_point.return_index {
+            las_point.return_number = (return_index + 1).clamp(0, MAX_RETURNS) as u8;
+        }
+        if let Some(return_count) = raw_point.return_count {
+            las_point.number_of_returns = return_count.clamp(0, MAX_RETURNS) as u8;
+        }
+
+        if extra_values.row.is_some() || extra_values.column.is_some() {
+            summary.attributes.has_row_column = true;
+        }
+        if extra_values.normal.is_some() {
+            summary.attributes.has_normals = true;
+        }
+        las_point.extra_bytes = extra_values.encode();
+        las_point.point_source_id = source_id;
+
+        summary.bounds.update(&las_point);
+        visit(las_point)?;
     }
 
-    Ok(CloudPoints {
-        points,
-        bounds,
-        has_color,
-        skipped_points,
-    })
+    Ok(summary)
+}
+
+/// Rotates a normal by the scan pose, then by the user transform, if any.
+fn rotate_normal(
+    normal: [f32; 3],
+    pose: Option<&RigidTransform>,
+    transform: Option<&RigidTransform>,
+) -> [f32; 3] {
+    let mut normal = normal.map(f64::from);
+    for rotation in [pose, transform].into_iter().flatten() {
+        normal = rotation.rotate(normal);
+    }
+    normal.map(|component| component as f32)
+}
+
+/// Reads a single point cloud from an E57 file and converts its points to LAS
+/// points, keeping all of them in memory.
+fn read_pointcloud(
+    source: Source,
+    intensity: &IntensityMapping,
+    options: &ConvertOptions,
+) -> Result<CloudPoints> {
+    let mut points: Vec<las::Point> = Vec::new();
+
+    let summary = visit_pointcloud(source, intensity, options, |point| {
+        points.push(point);
+        Ok(())
+    })?;
+
+    Ok(CloudPoints { points, summary })
+}
+
+/// First pass of a streaming conversion: reads a single point cloud to gather
+/// its bounds and attributes without keeping any point in memory.
+fn scan_pointcloud(
+    source: Source,
+    intensity: &IntensityMapping,
+    options: &ConvertOptions,
+) -> Result<CloudSummary> {
+    visit_pointcloud(source, intensity, options, |_| Ok(()))
+}
+
+/// Second pass of a streaming conversion: reads a single point cloud again and
+/// writes its points to `writer` in chunks of at most `buffer_size` points.
+fn stream_pointcloud(
+    source: Source,
+    writer: &mut las::Writer<BufWriter<File>>,
+    attributes: PointAttributes,
+    intensity: &IntensityMapping,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let buffer_size = options.buffer_size.max(1);
+    let mut buffer: Vec<las::Point> = Vec::with_capacity(buffer_size);
+    let format = *writer.header().point_format();
+    let extra_dimensions = extra_bytes::dimensions(&attributes);
+
+    visit_pointcloud(source, intensity, options, |mut point| {
+        fit_point_format(&mut point, &format, &extra_dimensions);
+        buffer.push(point);
+
+        if buffer.len() >= buffer_size {
+            writer.write_points(&buffer).context("Unable to write: ")?;
+            buffer.clear();
+        }
+
+        Ok(())
+    })?;
+
+    writer.write_points(&buffer).context("Unable to write: ")?;
+
+    Ok(())
+}
+
+fn report_skipped_points(index: usize, summary: &CloudSummary) {
+    if summary.skipped_points > 0 {
+        println!(
+            "Pointcloud {index}: skipped {} points with invalid or direction-only coordinates",
+            summary.skipped_points
+        );
+    }
+    if summary.flagged_points > 0 {
+        println!(
+            "Pointcloud {index}: flagged {} points with invalid coordinates or color",
+            summary.flagged_points
+        );
+    }
 }
 
 /// Converts a point cloud to a LAS file.
@@ -73,6 +360,8 @@ fn read_pointcloud(input_path: &Path, pointcloud: &PointCloud) -> Result<CloudPo
 /// - `output_path`: A reference to the output dir.
 /// - `las_version`: The LAS version used for the output file.
 ///
+/// See [`convert_pointcloud_with_options`] for the other conversion options.
+///
 /// # Example
 /// ```ignore
 /// use std::path::Path;
@@ -95,29 +384,146 @@ pub fn convert_pointcloud(
     output_path: &Path,
     las_version: &LasVersion,
 ) -> Result<()> {
-    let cloud = read_pointcloud(input_path, pointcloud)?;
+    let options = ConvertOptions {
+        las_version: *las_version,
+        ..Default::default()
+    };
 
-    if cloud.skipped_points > 0 {
-        println!(
-            "Pointcloud {index}: skipped {} points with invalid coordinates",
-            cloud.skipped_points
-        );
-    }
+    convert_pointcloud_with_options(index, pointcloud, input_path, output_path, &options)
+}
+
+/// Converts a point cloud to a LAS file using the given [`ConvertOptions`].
+///
+/// The points are written to `<output_path>/las/<index>.las` (or `<index>.laz`
+/// with `options.compress`, `<index>.copc.laz` with `options.copc`), or to the
+/// name given by `options.name_template`. With `options.streaming`, the point
+/// cloud is read twice (once for its bounds, once for its points) so that at
+/// most `options.buffer_size` points are held in memory.
+///
+/// # Parameters
+/// - `index`: The index of the point cloud.
+/// - `pointcloud`: A reference to the point cloud to be converted.
+/// - `input_path`: A reference to the input file path (E57 file).
+/// - `output_path`: A reference to the output dir.
+/// - `options`: The conversion options (LAS version, streaming, compression, ...).
+///
+/// # Example
+/// ```ignore
+/// use std::path::Path;
+/// use e57_to_las::{convert_pointcloud_with_options, ConvertOptions};
+///
+/// # fn example() -> anyhow::Result<()> {
+/// let input_path = Path::new("path/to/input.e57");
+/// let output_path = Path::new("path/to/output");
+/// let options = ConvertOptions {
+///     streaming: true,
+///     ..Default::default()
+/// };
+/// // pointcloud would be obtained from E57Reader in practice
+/// # let pointcloud = todo!();
+/// convert_pointcloud_with_options(0, &pointcloud, input_path, output_path, &options)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn convert_pointcloud_with_options(
+    index: usize,
+    pointcloud: &PointCloud,
+    input_path: &Path,
+    output_path: &Path,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let source = Source {
+        input_path,
+        pointcloud,
+        source_id: 0,
+    };
+    let file_stem = input_path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name =
+        options
+            .name_template
+            .file_name(index, pointcloud, &file_stem, options.output_extension());
+    convert_source(index, source, &file_name, output_path, options)
+}
 
-    let path = ensure_parent_dir(output_path.join("las").join(format!("{index}.las")))
+/// Converts the point cloud of `source` to `<output_path>/las/<file_name>`,
+/// like [`convert_pointcloud_with_options`].
+pub(crate) fn convert_source(
+    index: usize,
+    source: Source,
+    file_name: &str,
+    output_path: &Path,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let path = ensure_parent_dir(output_path.join("las").join(file_name))
         .context("Unable to create path: ")?;
 
-    let mut writer = get_las_writer(
-        pointcloud.guid.clone(),
+    let pointcloud = source.pointcloud;
+    let intensity = options.intensity.mapping([pointcloud]);
+
+    let e57_reader =
+        E57Reader::from_file(source.input_path).context("Failed to open e57 file: ")?;
+    let metadata = HeaderMetadata::new(&e57_reader, pointcloud.guid.clone(), &[pointcloud])?;
+    drop(e57_reader);
+
+    if options.streaming && !options.copc {
+        let summary = scan_pointcloud(source, &intensity, options)?;
+        report_skipped_points(index, &summary);
+
+        let mut writer =
+            get_las_writer(&metadata, path, summary.bounds, summary.attributes, options)
+                .context("Unable to create writer: ")?;
+
+        stream_pointcloud(source, &mut writer, summary.attributes, &intensity, options)?;
+
+        writer.close().context("Failed to close the writer: ")?;
+
+        return Ok(());
+    }
+
+    let cloud = read_pointcloud(source, &intensity, options)?;
+    report_skipped_points(index, &cloud.summary);
+
+    write_points(
+        &metadata,
         path,
-        cloud.bounds,
-        cloud.has_color,
-        las_version,
+        cloud.points,
+        cloud.summary.bounds,
+        cloud.summary.attributes,
+        options,
     )
-    .context("Unable to create writer: ")?;
+}
+
+/// Writes the points of fully read point clouds to `path`, either as a COPC
+/// file or through a regular LAS writer.
+fn write_points<I>(
+    metadata: &HeaderMetadata,
+    path: PathBuf,
+    points: I,
+    bounds: PointBounds,
+    attributes: PointAttributes,
+    options: &ConvertOptions,
+) -> Result<()>
+where
+    I: IntoIterator<Item = las::Point>,
+{
+    if options.copc {
+        return write_copc(
+            metadata,
+            path,
+            points.into_iter().collect(),
+            bounds,
+            attributes,
+            options,
+        );
+    }
 
-    for mut p in cloud.points {
-        backfill_color(&mut p, cloud.has_color);
+    let mut writer = get_las_writer(metadata, path, bounds, attributes, options)
+        .context("Unable to create writer: ")?;
+
+    let format = *writer.header().point_format();
+    let extra_dimensions = extra_bytes::dimensions(&attributes);
+    for mut p in points {
+        fit_point_format(&mut p, &format, &extra_dimensions);
         writer.write_point(p).context("Unable to write: ")?;
     }
 
@@ -126,6 +532,63 @@ pub fn convert_pointcloud(
     Ok(())
 }
 
+#[cfg(feature = "copc")]
+fn write_copc(
+    metadata: &HeaderMetadata,
+    path: PathBuf,
+    points: Vec<las::Point>,
+    bounds: PointBounds,
+    attributes: PointAttributes,
+    options: &ConvertOptions,
+) -> Result<()> {
+    crate::copc_writer::write_copc(metadata, path, points, bounds, attributes, options)
+        .context("Unable to write COPC file: ")
+}
+
+#[cfg(not(feature = "copc"))]
+fn write_copc(
+    _metadata: &HeaderMetadata,
+    _path: PathBuf,
+    _points: Vec<las::Point>,
+    _bounds: PointBounds,
+    _attributes: PointAttributes,
+    _options: &ConvertOptions,
+) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "COPC output requires e57-to-las to be built with the `copc` feature"
+    ))
+}
+
+/// Makes a converted point match the LAS point format of the output: the
+/// attributes the format has no room for are dropped, the missing ones are
+/// backfilled.
+pub(crate) fn fit_point_format(
+    point: &mut las::Point,
+    format: &las::point::Format,
+    extra_dimensions: &[ExtraDimension],
+) {
+    backfill_color(point, format.has_color);
+    backfill_gps_time(point, format.has_gps_time);
+    if !format.has_color {
+        point.color = None;
+    }
+    if !format.has_gps_time {
+        point.gps_time = None;
+    }
+    point.nir = format.has_nir.then(|| point.nir.unwrap_or_default());
+    clamp_returns(point, format.is_extended);
+    extra_bytes::select(point, extra_dimensions);
+}
+
+/// Clamps the return number and number of returns of a point to 7 for the
+/// legacy point formats, which only have 3 bits for them.
+fn clamp_returns(point: &mut las::Point, is_extended: bool) {
+    if !is_extended {
+        point.return_number = point.return_number.min(MAX_LEGACY_RETURNS);
+        point.number_of_returns = point.number_of_returns.min(MAX_LEGACY_RETURNS);
+    }
+}
+
 /// Backfills a default (black) color on points missing one when the LAS point
 /// format includes color, since `las` rejects points whose color presence does
 /// not match the point format.
@@ -135,45 +598,162 @@ fn backfill_color(point: &mut las::Point, has_color: bool) {
     }
 }
 
+/// Returns the path of the merged LAS file, creating its parent directory.
+fn las_output_path(output_path: &Path, options: &ConvertOptions) -> Result<PathBuf> {
+    ensure_parent_dir(
+        output_path
+            .join("las")
+            .join(format!("0.{}", options.output_extension())),
+    )
+    .context("Unable to create path: ")
+}
+
+/// Backfills a zero GPS time on points missing one when the LAS point format
+/// includes GPS time, for the same reason as [`backfill_color`].
+fn backfill_gps_time(point: &mut las::Point, has_gps_time: bool) {
+    if has_gps_time && point.gps_time.is_none() {
+        point.gps_time = Some(0.0);
+    }
+}
+
+/// Returns the offset added to the E57 timestamps of a point cloud: with
+/// `options.adjusted_gps_time`, its `acquisitionStart` GPS time minus 1e9, so
+/// that the LAS GPS times are adjusted standard GPS times; zero otherwise.
+fn gps_time_offset(pointcloud: &PointCloud, options: &ConvertOptions) -> Result<f64> {
+    if !options.adjusted_gps_time {
+        return Ok(0.0);
+    }
+
+    let start = pointcloud.acquisition_start.as_ref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Point cloud {:?} has no acquisition start date to offset its timestamps by",
+            pointcloud.guid
+        )
+    })?;
+
+    Ok(start.gps_time - ADJUSTED_GPS_TIME_OFFSET)
+}
+
 /// Converts all point clouds of an E57 file to a single merged LAS file.
 ///
 /// This function reads every point cloud of the E57 file at `input_path` in parallel
 /// (each worker opens its own reader), converts the points to LAS points using the
-/// `convert_point` function, and writes them all to `<output_path>/las/0.las`,
+/// `convert_point` function, and writes them all to `<output_path>/las/0.las`
+/// (or `0.laz` with `options.compress`, `0.copc.laz` with `options.copc`),
 /// preserving the point cloud order.
 ///
+/// With `options.potree`, the points are written as a Potree 2.0 octree in
+/// `<output_path>/potree/` instead, with the station positions embedded in its
+/// `metadata.json`.
+///
+/// With `options.streaming`, the parallel read only gathers the bounds and color
+/// presence of each point cloud; the points are then read a second time, one point
+/// cloud after the other, and streamed to the writer in chunks of at most
+/// `options.buffer_size` points, so memory usage no longer grows with the file size.
+///
 /// This function is internal to the crate; use [`crate::convert_file`] with
 /// `as_stations = false` for the public merged-conversion entry point.
 ///
 /// # Parameters
 /// - `input_path`: A reference to the input file path (E57 file).
 /// - `output_path`: A reference to the output dir.
-/// - `las_version`: The LAS version used for the output file.
+/// - `options`: The conversion options (LAS version, streaming, compression, ...).
 pub(crate) fn convert_pointclouds(
     input_path: &Path,
     output_path: &Path,
-    las_version: &LasVersion,
+    options: &ConvertOptions,
 ) -> Result<()> {
     let e57_reader = E57Reader::from_file(input_path).context("Failed to open e57 file: ")?;
     let pointclouds = e57_reader.pointclouds();
     let guid = e57_reader.guid().to_owned();
+    let pointcloud_refs = pointclouds.iter().collect::<Vec<_>>();
+    let mut metadata = HeaderMetadata::new(&e57_reader, Some(guid), &pointcloud_refs)?;
     drop(e57_reader);
 
-    let clouds = pointclouds
+    let source_ids = options.point_source_ids.resolve(&pointcloud_refs)?;
+    metadata.set_point_sources(&pointcloud_refs, &source_ids)?;
+
+    let sources = pointclouds
+        .iter()
+        .zip(source_ids)
+        .map(|(pointcloud, source_id)| Source {
+            input_path,
+            pointcloud,
+            source_id,
+        })
+        .collect::<Vec<_>>();
+
+    let name = input_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    convert_sources(&sources, &metadata, name, output_path, options)
+}
+
+/// Converts the point clouds of `sources`, which may come from several E57
+/// files, to a single merged LAS file (or Potree octree named `name`), like
+/// [`convert_pointclouds`]. The bounds, attributes and intensity mapping span
+/// all the sources.
+pub(crate) fn convert_sources(
+    sources: &[Source],
+    metadata: &HeaderMetadata,
+    name: String,
+    output_path: &Path,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let intensity = options
+        .intensity
+        .mapping(sources.iter().map(|source| source.pointcloud));
+
+    if options.streaming && !options.copc && !options.potree {
+        let path = las_output_path(output_path, options)?;
+
+        let summaries = sources
+            .par_iter()
+            .enumerate()
+            .map(|(index, source)| -> Result<CloudSummary> {
+                println!("Scanning pointcloud {index}...");
+
+                let summary = scan_pointcloud(*source, &intensity, options)
+                    .context(format!("Error while scanning pointcloud {index}"))?;
+                report_skipped_points(index, &summary);
+
+                Ok(summary)
+            })
+            .collect::<Result<Vec<CloudSummary>>>()
+            .context("Error while scanning pointcloud")?;
+
+        let mut bounds = PointBounds::default();
+        let mut attributes = PointAttributes::default();
+        for summary in &summaries {
+            bounds.merge(&summary.bounds);
+            attributes.merge(&summary.attributes);
+        }
+
+        let mut writer = get_las_writer(metadata, path, bounds, attributes, options)
+            .context("Unable to create writer: ")?;
+
+        for (index, source) in sources.iter().enumerate() {
+            println!("Saving pointcloud {index}...");
+
+            stream_pointcloud(*source, &mut writer, attributes, &intensity, options)
+                .context(format!("Error while converting pointcloud {index}"))?;
+        }
+
+        writer.close().context("Failed to close the writer: ")?;
+        return Ok(());
+    }
+
+    let clouds = sources
         .par_iter()
         .enumerate()
-        .map(|(index, pointcloud)| -> Result<CloudPoints> {
+        .map(|(index, source)| -> Result<CloudPoints> {
             println!("Saving pointcloud {index}...");
 
-            let cloud = read_pointcloud(input_path, pointcloud)
+            let cloud = read_pointcloud(*source, &intensity, options)
                 .context(format!("Error while converting pointcloud {index}"))?;
-
-            if cloud.skipped_points > 0 {
-                println!(
-                    "Pointcloud {index}: skipped {} points with invalid coordinates",
-                    cloud.skipped_points
-                );
-            }
+            report_skipped_points(index, &cloud.summary);
 
             Ok(cloud)
         })
@@ -181,31 +761,73 @@ pub(crate) fn convert_pointclouds(
         .context("Error while converting pointcloud")?;
 
     let mut bounds = PointBounds::default();
+    let mut attributes = PointAttributes::default();
     for cloud in &clouds {
-        bounds.merge(&cloud.bounds);
+        bounds.merge(&cloud.summary.bounds);
+        attributes.merge(&cloud.summary.attributes);
     }
-    let has_color = clouds.iter().any(|cloud| cloud.has_color);
-
-    let path = ensure_parent_dir(output_path.join("las").join("0.las"))
-        .context("Unable to create path: ")?;
 
-    let mut writer = get_las_writer(Some(guid), path, bounds, has_color, las_version)
-        .context("Unable to create writer: ")?;
-
-    for cloud in clouds {
-        for mut p in cloud.points {
-            backfill_color(&mut p, has_color);
-            writer.write_point(p).context("Unable to write: ")?;
-        }
+    let points = clouds.into_iter().flat_map(|cloud| cloud.points);
+
+    if options.potree {
+        let projection = options
+            .crs
+            .as_ref()
+            .map(Crs::wkt)
+            .transpose()?
+            .unwrap_or_default();
+
+        let pointclouds = sources
+            .iter()
+            .map(|source| source.pointcloud.clone())
+            .collect::<Vec<_>>();
+
+        return write_potree(
+            &output_path.join("potree"),
+            name,
+            projection,
+            points.collect(),
+            bounds,
+            attributes.has_color,
+            station_positions(&pointclouds, options),
+        )
+        .context("Unable to write Potree octree: ");
     }
 
-    writer.close().context("Failed to close the writer: ")?;
-    Ok(())
+    write_points(
+        metadata,
+        las_output_path(output_path, options)?,
+        points,
+        bounds,
+        attributes,
+        options,
+    )
 }
 
 #[cfg(test)]
+#[allow(clippy::expect_used)]
 mod tests {
-    use super::backfill_color;
+    use super::{Source, backfill_color, clamp_returns, read_pointcloud};
+    use crate::test_utils::{cartesian_prototype, write_e57};
+    use crate::{ConvertOptions, DirectionPolicy, IntensityMode, InvalidPoints};
+    use e57::{E57Reader, Record, RecordDataType, RecordName, RecordValue};
+    use std::path::Path;
+
+    /// Writes an E57 file with one point cloud whose points are at (1, 1, 1),
+    /// with one more record holding `values`.
+    fn write_e57_record(path: &Path, record: Record, values: &[RecordValue]) {
+        let mut prototype = cartesian_prototype();
+        prototype.push(record);
+        let points = values
+            .iter()
+            .map(|value| {
+                let mut point = vec![RecordValue::Double(1.0); 3];
+                point.push(value.clone());
+                point
+            })
+            .collect();
+        write_e57(path, prototype, points);
+    }
 
     #[test]
     fn test_backfill_color_adds_default_when_format_has_color() {
@@ -238,4 +860,126 @@ mod tests {
 
         assert!(point.color.is_none());
     }
+
+    #[test]
+    fn test_clamp_returns_to_legacy_formats() {
+        let mut point = las::Point {
+            return_number: 12,
+            number_of_returns: 15,
+            ..Default::default()
+        };
+
+        clamp_returns(&mut point, false);
+
+        assert_eq!(point.return_number, 7);
+        assert_eq!(point.number_of_returns, 7);
+    }
+
+    #[test]
+    fn test_clamp_returns_keeps_extended_formats() {
+        let mut point = las::Point {
+            return_number: 12,
+            number_of_returns: 15,
+            ..Default::default()
+        };
+
+        clamp_returns(&mut point, true);
+
+        assert_eq!(point.return_number, 12);
+        assert_eq!(point.number_of_returns, 15);
+    }
+
+    #[test]
+    fn test_grey_from_integer_intensity_is_not_saturated() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("intensity.e57");
+        write_e57_record(
+            &path,
+            Record {
+                name: RecordName::Intensity,
+                data_type: RecordDataType::Integer { min: 0, max: 2047 },
+            },
+            &[0, 1023, 2047].map(RecordValue::Integer),
+        );
+
+        let e57_reader = E57Reader::from_file(&path).expect("Failed to open E57 file");
+        let pointclouds = e57_reader.pointclouds();
+        let source = Source {
+            input_path: &path,
+            pointcloud: &pointclouds[0],
+            source_id: 0,
+        };
+
+        for mode in [IntensityMode::Normalize, IntensityMode::Raw] {
+            let options = ConvertOptions {
+                intensity: mode,
+                ..Default::default()
+            };
+            let intensity = mode.mapping([&pointclouds[0]]);
+            let cloud =
+                read_pointcloud(source, &intensity, &options).expect("Failed to read points");
+
+            assert!(cloud.summary.attributes.has_color);
+            let greys = cloud
+                .points
+                .iter()
+                .map(|point| {
+                    let color = point.color.expect("Missing grey color");
+                    assert_eq!(color.red, color.green);
+                    assert_eq!(color.red, color.blue);
+                    color.red
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(greys[0], 0);
+            assert!((32_000..33_500).contains(&greys[1]), "{greys:?}");
+            assert_eq!(greys[2], u16::MAX);
+        }
+    }
+
+    #[test]
+    fn test_invalid_points_keep_the_direction_policy() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("directions.e57");
+        // A direction-only point and one without coordinates between valid
+        // ones (4 points, since the e57 crate reads whole bytes of 2-bit states).
+        write_e57_record(
+            &path,
+            Record {
+                name: RecordName::CartesianInvalidState,
+                data_type: RecordDataType::Integer { min: 0, max: 2 },
+            },
+            &[0, 1, 2, 0].map(RecordValue::Integer),
+        );
+
+        let e57_reader = E57Reader::from_file(&path).expect("Failed to open E57 file");
+        let pointclouds = e57_reader.pointclouds();
+        let source = Source {
+            input_path: &path,
+            pointcloud: &pointclouds[0],
+            source_id: 0,
+        };
+        let read = |direction_points| {
+            let options = ConvertOptions {
+                direction_points,
+                invalid_points: InvalidPoints::Withheld,
+                ..Default::default()
+            };
+            let intensity = options.intensity.mapping([&pointclouds[0]]);
+            read_pointcloud(source, &intensity, &options).expect("Failed to read points")
+        };
+
+        let cloud = read(DirectionPolicy::Drop);
+        assert_eq!(cloud.points.len(), 3);
+        assert_eq!(cloud.summary.skipped_points, 1);
+        assert_eq!(cloud.summary.flagged_points, 1);
+        assert_eq!(
+            [cloud.points[1].x, cloud.points[1].y, cloud.points[1].z],
+            [0.0; 3]
+        );
+
+        let cloud = read(DirectionPolicy::Withheld);
+        assert_eq!(cloud.points.len(), 4);
+        assert_eq!(cloud.summary.skipped_points, 0);
+        assert_eq!(cloud.summary.flagged_points, 1);
+    }
 }
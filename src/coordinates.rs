@@ -0,0 +1,204 @@
+use e57::{CartesianCoordinate, SphericalCoordinate};
+
+use crate::{Error, Result};
+
+/// What to do with the E57 points that only have a direction, i.e. whose
+/// range is unknown (e.g. laser pulses without return).
+///
+/// # Example
+/// ```
+/// use e57_to_las::DirectionPolicy;
+///
+/// assert_eq!(
+///     DirectionPolicy::try_from("project:100").ok(),
+///     Some(DirectionPolicy::Project(100.0))
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DirectionPolicy {
+    /// Skips them, like the points with invalid coordinates.
+    #[default]
+    Drop,
+    /// Places them along their direction at the given range from the scanner,
+    /// e.g. its maximum range.
+    Project(f64),
+    /// Places them along their direction at 1 m from the scanner, flagged as
+    /// withheld so that they are ignored by most LAS readers.
+    Withheld,
+}
+
+impl TryFrom<&str> for DirectionPolicy {
+    type Error = Error;
+
+    /// Parses `drop`, `withheld` or `project:<range>`.
+    fn try_from(value: &str) -> Result<Self> {
+        match value.trim() {
+            "drop" => Ok(DirectionPolicy::Drop),
+            "withheld" => Ok(DirectionPolicy::Withheld),
+            value => value
+                .strip_prefix("project:")
+                .and_then(|range| range.trim().parse::<f64>().ok())
+                .filter(|range| range.is_finite() && *range > 0.0)
+                .map(DirectionPolicy::Project)
+                .ok_or_else(|| {
+                    Error::InvalidDirectionPolicy(format!(
+                        "{value:?}, expected `drop`, `withheld` or `project:<range>` with a positive range"
+                    ))
+                }),
+        }
+    }
+}
+
+/// The coordinates of an E57 point in the local frame of its scanner, from its
+/// cartesian coordinates or, when they are not valid, its spherical ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LocalPosition {
+    Valid([f64; 3]),
+    /// A unit direction vector.
+    Direction([f64; 3]),
+    Invalid,
+}
+
+impl LocalPosition {
+    pub(crate) fn of(point: &e57::Point) -> Self {
+        match (&point.cartesian, &point.spherical) {
+            (CartesianCoordinate::Valid { x, y, z }, _) => LocalPosition::Valid([*x, *y, *z]),
+            (
+                _,
+                SphericalCoordinate::Valid {
+                    range,
+                    azimuth,
+                    elevation,
+                },
+            ) => LocalPosition::Valid(spherical_to_cartesian(*range, *azimuth, *elevation)),
+            (CartesianCoordinate::Direction { x, y, z }, _) => {
+                // E57 direction vectors need not be normalized.
+                let norm = (x * x + y * y + z * z).sqrt();
+                if norm > 0.0 && norm.is_finite() {
+                    LocalPosition::Direction([x / norm, y / norm, z / norm])
+                } else {
+                    LocalPosition::Invalid
+                }
+            }
+            (_, SphericalCoordinate::Direction { azimuth, elevation }) => {
+                LocalPosition::Direction(spherical_to_cartesian(1.0, *azimuth, *elevation))
+            }
+            _ => LocalPosition::Invalid,
+        }
+    }
+
+    /// Resolves the position according to `policy`, returning the coordinates
+    /// and whether the point must be flagged as withheld, or `None` if the
+    /// point must be skipped.
+    pub(crate) fn resolve(self, policy: DirectionPolicy) -> Option<([f64; 3], bool)> {
+        match (self, policy) {
+            (LocalPosition::Valid(position), _) => Some((position, false)),
+            (LocalPosition::Direction(direction), DirectionPolicy::Project(range)) => {
+                Some((direction.map(|component| component * range), false))
+            }
+            (LocalPosition::Direction(direction), DirectionPolicy::Withheld) => {
+                Some((direction, true))
+            }
+            (LocalPosition::Direction(_), DirectionPolicy::Drop) | (LocalPosition::Invalid, _) => {
+                None
+            }
+        }
+    }
+}
+
+/// Converts E57 spherical coordinates (azimuth from the x axis towards the y
+/// axis, elevation from the xy plane towards z, both in radians) to cartesian.
+fn spherical_to_cartesian(range: f64, azimuth: f64, elevation: f64) -> [f64; 3] {
+    let horizontal = range * elevation.cos();
+    [
+        horizontal * azimuth.cos(),
+        horizontal * azimuth.sin(),
+        range * elevation.sin(),
+    ]
+}
+
+#[cfg(test)]
+#[allow(clippy::panic)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn point(cartesian: CartesianCoordinate, spherical: SphericalCoordinate) -> e57::Point {
+        e57::Point {
+            cartesian,
+            spherical,
+            color: None,
+            intensity: None,
+            row: -1,
+            column: -1,
+        }
+    }
+
+    fn assert_close(actual: [f64; 3], expected: [f64; 3]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+        }
+    }
+
+    #[test]
+    fn test_parse_direction_policy() {
+        assert_eq!(
+            DirectionPolicy::try_from("drop").ok(),
+            Some(DirectionPolicy::Drop)
+        );
+        assert_eq!(
+            DirectionPolicy::try_from("withheld").ok(),
+            Some(DirectionPolicy::Withheld)
+        );
+        assert!(DirectionPolicy::try_from("project:0").is_err());
+        assert!(DirectionPolicy::try_from("project").is_err());
+    }
+
+    #[test]
+    fn test_spherical_only_point() {
+        let spherical = point(
+            CartesianCoordinate::Invalid,
+            SphericalCoordinate::Valid {
+                range: 2.0,
+                azimuth: FRAC_PI_2,
+                elevation: 0.0,
+            },
+        );
+
+        let Some((position, false)) = LocalPosition::of(&spherical).resolve(DirectionPolicy::Drop)
+        else {
+            panic!("Expected a valid position");
+        };
+        assert_close(position, [0.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn test_direction_policies() {
+        let direction = point(
+            CartesianCoordinate::Direction {
+                x: 0.0,
+                y: 0.0,
+                z: 5.0,
+            },
+            SphericalCoordinate::Invalid,
+        );
+        let position = LocalPosition::of(&direction);
+        assert_eq!(position, LocalPosition::Direction([0.0, 0.0, 1.0]));
+
+        assert_eq!(position.resolve(DirectionPolicy::Drop), None);
+        assert_eq!(
+            position.resolve(DirectionPolicy::Project(50.0)),
+            Some(([0.0, 0.0, 50.0], false))
+        );
+        assert_eq!(
+            position.resolve(DirectionPolicy::Withheld),
+            Some(([0.0, 0.0, 1.0], true))
+        );
+
+        let invalid = point(CartesianCoordinate::Invalid, SphericalCoordinate::Invalid);
+        assert_eq!(
+            LocalPosition::of(&invalid).resolve(DirectionPolicy::Withheld),
+            None
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use las::copc::{CopcHierarchyVlr, CopcInfoVlr, Entry, VoxelKey};
+use laz::{
+    LazVlr, LazVlrBuilder,
+    laszip::{ChunkTable, ChunkTableEntry},
+    record::{LayeredPointRecordCompressor, RecordCompressor},
+};
+
+use crate::convert_pointcloud::fit_point_format;
+use crate::get_las_writer::{PointAttributes, PointBounds, gps_time_type, las_transforms};
+use crate::header_metadata::HeaderMetadata;
+use crate::octree::{self, Cube, OctreeNode, build_octree};
+use crate::{ConvertOptions, extra_bytes};
+
+/// Size in bytes of the COPC info VLR payload.
+const COPC_INFO_SIZE: usize = 160;
+/// Position of the root hierarchy page offset and size in the COPC info VLR
+/// payload: `las` keeps these fields private, so they are patched in place.
+const ROOT_HIERARCHY_RANGE: std::ops::Range<usize> = 40..56;
+/// Size in bytes of an extended VLR header.
+const EVLR_HEADER_SIZE: u64 = 60;
+/// Size in bytes of a hierarchy entry.
+const ENTRY_SIZE: usize = 32;
+
+fn copc_info_data(
+    cube: &Cube,
+    root_hierarchy_offset: u64,
+    root_hierarchy_size: u64,
+    gps_time_range: (f64, f64),
+) -> Result<Vec<u8>> {
+    let mut copc_info = CopcInfoVlr::try_from(&copc_info_vlr(vec![0; COPC_INFO_SIZE]))?;
+    copc_info.center_x = cube.center.x;
+    copc_info.center_y = cube.center.y;
+    copc_info.center_z = cube.center.z;
+    copc_info.halfsize = cube.halfsize;
+    copc_info.spacing = cube.spacing();
+    copc_info.gpstime_minimum = gps_time_range.0;
+    copc_info.gpstime_maximum = gps_time_range.1;
+
+    let mut data = Vec::with_capacity(COPC_INFO_SIZE);
+    copc_info.write_to(&mut data)?;
+    data[ROOT_HIERARCHY_RANGE].copy_from_slice(
+        &[
+            root_hierarchy_offset.to_le_bytes(),
+            root_hierarchy_size.to_le_bytes(),
+        ]
+        .concat(),
+    );
+    Ok(data)
+}
+
+fn copc_info_vlr(data: Vec<u8>) -> las::Vlr {
+    las::Vlr {
+        user_id: String::from("copc"),
+        record_id: CopcInfoVlr::RECORD_ID,
+        description: String::from("copc info"),
+        data,
+    }
+}
+
+/// Returns the COPC key of an octree node, whose levels and grid positions
+/// follow the same EPT scheme.
+fn copc_key(key: &octree::VoxelKey) -> VoxelKey {
+    VoxelKey {
+        l: key.level,
+        x: key.x,
+        y: key.y,
+        z: key.z,
+    }
+}
+
+/// Returns the hierarchy page listing `entries`, since `las` only writes whole
+/// hierarchy VLRs it has read.
+fn hierarchy_page(entries: &[Entry]) -> Vec<u8> {
+    let mut page = Vec::with_capacity(entries.len() * ENTRY_SIZE);
+    for entry in entries {
+        page.extend_from_slice(&entry.key.l.to_le_bytes());
+        page.extend_from_slice(&entry.key.x.to_le_bytes());
+        page.extend_from_slice(&entry.key.y.to_le_bytes());
+        page.extend_from_slice(&entry.key.z.to_le_bytes());
+        page.extend_from_slice(&entry.offset.to_le_bytes());
+        page.extend_from_slice(&entry.byte_size.to_le_bytes());
+        page.extend_from_slice(&entry.point_count.to_le_bytes());
+    }
+    page
+}
+
+/// Writes the points as a COPC 1.0 file: a LAS 1.4 file with point format 6
+/// (or 7 with color, or the format of `options`), LAZ compressed with one chunk per octree node, whose
+/// octree is described by the `copc info` VLR and the hierarchy EVLR.
+///
+/// The octree root is the cube enclosing `bounds`; nodes are subdivided until
+/// their point spacing reaches the LAS scale of the output file. The CRS, scale
+/// and offset of `options` are honored like for regular LAS files.
+pub(crate) fn write_copc(
+    metadata: &HeaderMetadata,
+    output_path: PathBuf,
+    points: Vec<las::Point>,
+    bounds: PointBounds,
+    attributes: PointAttributes,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let mut builder = las::Builder::from((1, 4));
+    builder.point_format = options
+        .point_format
+        .resolve(builder.version, true, &attributes)?;
+    builder.point_format.is_compressed = true;
+    builder.generating_software = String::from("e57_to_las");
+    builder.transforms = las_transforms(&bounds, &options.scale, &options.offset)?;
+    builder.gps_time_type = gps_time_type(options);
+
+    let extra_dimensions = extra_bytes::dimensions(&attributes);
+    builder.point_format.extra_bytes = extra_bytes::size(&extra_dimensions);
+
+    let point_format = builder.point_format;
+    let transforms = builder.transforms;
+
+    let laz_vlr = LazVlrBuilder::default()
+        .with_point_format(point_format.to_u8()?, point_format.extra_bytes)?
+        .with_variable_chunk_size()
+        .build();
+    let mut laz_vlr_data = Vec::new();
+    laz_vlr.write_to(&mut laz_vlr_data)?;
+
+    // The copc info VLR must be the first VLR; its content is only known once
+    // the points are written, so a zeroed placeholder is written first.
+    builder.vlrs.push(copc_info_vlr(vec![0; COPC_INFO_SIZE]));
+    builder.vlrs.push(las::Vlr {
+        user_id: String::from("laszip encoded"),
+        record_id: 22204,
+        description: String::from("https://crates.io/crates/laz"),
+        data: laz_vlr_data,
+    });
+    builder.vlrs.extend(extra_bytes::vlr(&extra_dimensions));
+    metadata.add_to(&mut builder);
+    if let Some(crs) = &options.crs {
+        crs.add_to(&mut builder)
+            .context("Unable to write the CRS: ")?;
+    }
+
+    let mut header = builder.into_header().context("Error encountered: ")?;
+
+    let mut gps_time_range = (f64::INFINITY, f64::NEG_INFINITY);
+    let points: Vec<las::Point> = points
+        .into_iter()
+        .map(|mut point| {
+            fit_point_format(&mut point, &point_format, &extra_dimensions);
+            let gps_time = point.gps_time.unwrap_or_default();
+            gps_time_range = (
+                gps_time_range.0.min(gps_time),
+                gps_time_range.1.max(gps_time),
+            );
+            header.add_point(&point);
+            point
+        })
+        .collect();
+    if points.is_empty() {
+        gps_time_range = (0.0, 0.0);
+    }
+
+    let cube = Cube::from_bounds(&bounds);
+    let resolution = transforms
+        .x
+        .scale
+        .min(transforms.y.scale)
+        .min(transforms.z.scale);
+    let nodes = build_octree(points, &cube, cube.max_depth(resolution));
+
+    let file = File::create(&output_path)
+        .with_context(|| format!("Unable to create {}", output_path.display()))?;
+    let mut writer = BufWriter::new(file);
+    header
+        .write_to(&mut writer)
+        .context("Error encountered: ")?;
+
+    let (hierarchy, chunk_table) =
+        write_chunks(&mut writer, &nodes, &laz_vlr, &point_format, &transforms)?;
+
+    let chunk_table_offset = writer.stream_position()?;
+    chunk_table.write_to(&mut writer, &laz_vlr)?;
+
+    let start_of_first_evlr = writer.stream_position()?;
+    let hierarchy = hierarchy_page(&hierarchy);
+    let hierarchy_size = hierarchy.len() as u64;
+    las::Vlr {
+        user_id: String::from("copc"),
+        record_id: CopcHierarchyVlr::RECORD_ID,
+        description: String::from("EPT hierarchy"),
+        data: hierarchy,
+    }
+    .into_raw(true)?
+    .write_to(&mut writer)?;
+    for evlr in header.evlrs() {
+        evlr.clone().into_raw(true)?.write_to(&mut writer)?;
+    }
+
+    // Rewrite the header now that the copc info and EVLR offset are known.
+    let mut raw_header = header.clone().into_raw()?;
+    raw_header.evlr = Some(las::raw::header::Evlr {
+        start_of_first_evlr,
+        number_of_evlrs: 1 + header.evlrs().len() as u32,
+    });
+    writer.seek(SeekFrom::Start(0))?;
+    raw_header.write_to(&mut writer)?;
+    let copc_info = copc_info_data(
+        &cube,
+        start_of_first_evlr + EVLR_HEADER_SIZE,
+        hierarchy_size,
+        gps_time_range,
+    )?;
+    copc_info_vlr(copc_info)
+        .into_raw(false)?
+        .write_to(&mut writer)?;
+
+    // Point the LAZ data to its chunk table.
+    writer.seek(SeekFrom::Start(u64::from(raw_header.offset_to_point_data)))?;
+    writer.write_all(&(chunk_table_offset as i64).to_le_bytes())?;
+
+    writer.flush().context("Failed to close the writer: ")?;
+
+    Ok(())
+}
+
+/// Writes every octree node as its own LAZ chunk, returning the hierarchy
+/// entries describing the nodes and the LAZ chunk table.
+fn write_chunks<W: Write + Seek + Send + Sync>(
+    writer: &mut W,
+    nodes: &BTreeMap<octree::VoxelKey, OctreeNode>,
+    laz_vlr: &LazVlr,
+    point_format: &las::point::Format,
+    transforms: &las::Vector<las::Transform>,
+) -> Result<(Vec<Entry>, ChunkTable)> {
+    // Placeholder for the offset to the chunk table.
+    writer.write_all(&(-1_i64).to_le_bytes())?;
+
+    let mut hierarchy = Vec::with_capacity(nodes.len());
+    let mut chunk_table = ChunkTable::with_capacity(nodes.len());
+    let mut uncompressed = Vec::new();
+
+    for (key, node) in nodes {
+        uncompressed.clear();
+        for point in &node.points {
+            point
+                .clone()
+                .into_raw(transforms)?
+                .write_to(&mut uncompressed, point_format)?;
+        }
+
+        let offset = writer.stream_position()?;
+        let mut compressor = LayeredPointRecordCompressor::new(&mut *writer);
+        compressor.set_fields_from(laz_vlr.items())?;
+        compressor.compress_many(&uncompressed)?;
+        compressor.done()?;
+        let byte_size = compressor.get_mut().stream_position()? - offset;
+
+        chunk_table.push(ChunkTableEntry {
+            point_count: node.points.len() as u64,
+            byte_count: byte_size,
+        });
+
+        hierarchy.push(Entry {
+            key: copc_key(key),
+            offset,
+            byte_size: i32::try_from(byte_size)?,
+            point_count: i32::try_from(node.points.len())?,
+        });
+    }
+
+    Ok((hierarchy, chunk_table))
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    fn point(x: f64, y: f64, z: f64) -> las::Point {
+        las::Point {
+            x,
+            y,
+            z,
+            ..Default::default()
+        }
+    }
+
+    fn entry_reader(path: &std::path::Path) -> las::CopcEntryReader<'static, BufReader<File>> {
+        let file = File::open(path).expect("Failed to open COPC file");
+        las::CopcEntryReader::new(BufReader::new(file)).expect("Failed to read COPC file")
+    }
+
+    #[test]
+    fn test_write_copc_hierarchy() {
+        // A dense patch of 1 mm spaced points in a 1 m cube fills several
+        // octree levels at the automatic 1 mm scale.
+        let mut points = vec![point(0.0, 0.0, 0.0), point(1.0, 1.0, 1.0)];
+        for i in 0..100 {
+            for j in 0..100 {
+                points.push(point(f64::from(i) * 0.001, f64::from(j) * 0.001, 0.5));
+            }
+        }
+        let mut bounds = PointBounds::default();
+        for point in &points {
+            bounds.update(point);
+        }
+
+        let cube = Cube::from_bounds(&bounds);
+        let expected_counts: std::collections::HashMap<VoxelKey, usize> =
+            build_octree(points.clone(), &cube, cube.max_depth(0.001))
+                .iter()
+                .map(|(key, node)| (copc_key(key), node.points.len()))
+                .collect();
+
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("cloud.copc.laz");
+        write_copc(
+            &HeaderMetadata::default(),
+            path.clone(),
+            points,
+            bounds,
+            PointAttributes::default(),
+            &ConvertOptions::default(),
+        )
+        .expect("Failed to write COPC file");
+
+        let reader = entry_reader(&path);
+        let number_of_points = reader.header().number_of_points();
+        let entries = reader.hierarchy_entries().expect("Missing COPC hierarchy");
+        let max_level = entries.iter().map(|entry| entry.key.l).max();
+        assert!(max_level >= Some(2), "{max_level:?}");
+        assert_eq!(entries.len(), expected_counts.len());
+
+        let mut total = 0;
+        for entry in entries {
+            // The reader keeps its decompressor state across chunks, so every
+            // chunk is read with a new reader.
+            let mut reader = entry_reader(&path);
+            let copc_info = reader.header().copc_info_vlr();
+            let bounds = entry.key.bounds(copc_info.expect("Missing COPC info"));
+
+            let mut points = Vec::new();
+            let count = reader
+                .read_entry_points(&entry, &mut points)
+                .expect("Failed to read entry points");
+            assert_eq!(
+                Some(&(entry.point_count as usize)),
+                expected_counts.get(&entry.key)
+            );
+            assert_eq!(points.len() as u64, count);
+            for point in &points {
+                assert!(
+                    (bounds.min.x..=bounds.max.x).contains(&point.x)
+                        && (bounds.min.y..=bounds.max.y).contains(&point.y)
+                        && (bounds.min.z..=bounds.max.z).contains(&point.z),
+                    "{:?} outside {:?}",
+                    point,
+                    entry.key
+                );
+            }
+            total += count;
+        }
+        assert_eq!(total, number_of_points);
+    }
+}
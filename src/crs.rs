@@ -0,0 +1,294 @@
+use crate::{Error, Result};
+
+/// User id of the LAS VLRs holding coordinate reference system definitions.
+const PROJECTION_USER_ID: &str = "LASF_Projection";
+/// Record id of the OGC WKT coordinate system VLR (LAS 1.4).
+const WKT_RECORD_ID: u16 = 2112;
+/// Record id of the GeoTIFF GeoKeyDirectoryTag VLR.
+const GEO_KEY_DIRECTORY_RECORD_ID: u16 = 34735;
+
+/// GeoTIFF key of the model type (projected, geographic or geocentric).
+const GT_MODEL_TYPE_GEO_KEY: u16 = 1024;
+/// GeoTIFF key of the raster type, always `RasterPixelIsArea` for point clouds.
+const GT_RASTER_TYPE_GEO_KEY: u16 = 1025;
+/// GeoTIFF key of the EPSG code of a geographic or geocentric CRS.
+const GEOGRAPHIC_TYPE_GEO_KEY: u16 = 2048;
+/// GeoTIFF key of the EPSG code of a projected CRS.
+const PROJECTED_CS_TYPE_GEO_KEY: u16 = 3072;
+
+/// Coordinate reference system written in the header of the LAS files.
+///
+/// LAS 1.4 files get an OGC WKT VLR (and the WKT global encoding bit), older
+/// versions get GeoTIFF keys, which can only express EPSG codes.
+///
+/// # Example
+/// ```
+/// use e57_to_las::Crs;
+///
+/// assert_eq!(Crs::try_from("EPSG:2056").ok(), Some(Crs::Epsg(2056)));
+/// assert_eq!(Crs::try_from("e57").ok(), Some(Crs::FromE57));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Crs {
+    /// A horizontal EPSG code, e.g. `2056` for CH1903+ / LV95.
+    Epsg(u16),
+    /// An OGC WKT coordinate system definition.
+    Wkt(String),
+    /// The coordinate system described by the `coordinateMetadata` of the E57
+    /// file, when it holds an EPSG code or a WKT definition.
+    FromE57,
+}
+
+impl Crs {
+    /// Resolves [`Crs::FromE57`] against the coordinate metadata of the E57
+    /// file, returning `None` (with a warning) when it holds no usable CRS.
+    pub(crate) fn resolve(&self, coordinate_metadata: Option<&str>) -> Option<Crs> {
+        if *self != Crs::FromE57 {
+            return Some(self.clone());
+        }
+
+        let Some(metadata) = coordinate_metadata.filter(|metadata| !metadata.trim().is_empty())
+        else {
+            eprintln!("Warning: the E57 file has no coordinate metadata, no CRS will be written");
+            return None;
+        };
+
+        match Crs::try_from(metadata) {
+            Ok(Crs::FromE57) | Err(_) => {
+                eprintln!(
+                    "Warning: the E57 coordinate metadata {metadata:?} is neither an EPSG code nor a WKT definition, no CRS will be written"
+                );
+                None
+            }
+            Ok(crs) => Some(crs),
+        }
+    }
+
+    /// Returns the WKT definition of this CRS.
+    pub(crate) fn wkt(&self) -> anyhow::Result<String> {
+        match self {
+            Crs::Epsg(code) => crs_definitions::from_code(*code)
+                .map(|definition| definition.wkt.to_owned())
+                .ok_or_else(|| anyhow::anyhow!("Unknown horizontal EPSG code {code}")),
+            Crs::Wkt(wkt) => Ok(wkt.clone()),
+            Crs::FromE57 => Err(anyhow::anyhow!(
+                "The E57 coordinate system must be resolved before being written"
+            )),
+        }
+    }
+
+    /// Returns the EPSG code of this CRS, if it has one.
+    pub(crate) fn epsg(&self) -> Option<u16> {
+        match self {
+            Crs::Epsg(code) => Some(*code),
+            Crs::Wkt(wkt) => epsg_from_wkt(wkt),
+            Crs::FromE57 => None,
+        }
+    }
+
+    /// Adds the VLR describing this CRS to `builder`: an OGC WKT VLR for LAS
+    /// 1.4, or GeoTIFF keys for older versions.
+    pub(crate) fn add_to(&self, builder: &mut las::Builder) -> anyhow::Result<()> {
+        let vlr = if builder.version >= las::Version::new(1, 4) {
+            builder.has_wkt_crs = true;
+            wkt_vlr(&self.wkt()?)
+        } else {
+            let code = match self {
+                Crs::Epsg(code) => *code,
+                _ => epsg_from_wkt(&self.wkt()?).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "LAS {} stores the CRS as GeoTIFF keys, which need an EPSG code; use LAS 1.4 to write a WKT without EPSG authority",
+                        builder.version
+                    )
+                })?,
+            };
+            geo_key_directory_vlr(code, &Crs::Epsg(code).wkt()?)
+        };
+
+        builder.vlrs.push(vlr);
+        Ok(())
+    }
+}
+
+impl TryFrom<&str> for Crs {
+    type Error = Error;
+
+    /// Parses `e57`, an EPSG code (`EPSG:2056` or `2056`) or a WKT definition.
+    fn try_from(value: &str) -> Result<Self> {
+        let value = value.trim();
+
+        if value.eq_ignore_ascii_case("e57") {
+            return Ok(Crs::FromE57);
+        }
+
+        if value.contains('[') {
+            return Ok(Crs::Wkt(value.to_owned()));
+        }
+
+        let code = value
+            .strip_prefix("EPSG:")
+            .or_else(|| value.strip_prefix("epsg:"))
+            .unwrap_or(value);
+        code.parse::<u16>().map(Crs::Epsg).map_err(|_| {
+            Error::InvalidCrs(format!(
+                "{value:?} is not an EPSG code (e.g. EPSG:2056), a WKT definition or `e57`"
+            ))
+        })
+    }
+}
+
+/// Returns the EPSG code of the outermost `AUTHORITY` (WKT1) or `ID` (WKT2)
+/// of a WKT definition, which is always the last one.
+fn epsg_from_wkt(wkt: &str) -> Option<u16> {
+    let start = rfind_keyword(wkt, "AUTHORITY[").max(rfind_keyword(wkt, "ID["))?;
+    let mut parts = wkt[start..].split(']').next()?.split(',');
+
+    let authority = parts.next()?.trim().trim_matches('"');
+    if !authority.eq_ignore_ascii_case("EPSG") {
+        return None;
+    }
+    parts.next()?.trim().trim_matches('"').parse().ok()
+}
+
+/// Returns the index following the last occurrence of a WKT keyword, skipping
+/// the longer keywords ending like it (e.g. `GRID[` for `ID[`).
+fn rfind_keyword(wkt: &str, keyword: &str) -> Option<usize> {
+    wkt.rmatch_indices(keyword)
+        .find(|(index, _)| {
+            wkt[..*index]
+                .chars()
+                .next_back()
+                .is_none_or(|c| c == ',' || c == '[' || c.is_whitespace())
+        })
+        .map(|(index, _)| index + keyword.len())
+}
+
+fn wkt_vlr(wkt: &str) -> las::Vlr {
+    // The WKT string is null terminated.
+    let mut data = wkt.as_bytes().to_vec();
+    data.push(0);
+
+    las::Vlr {
+        user_id: String::from(PROJECTION_USER_ID),
+        record_id: WKT_RECORD_ID,
+        description: String::from("OGC Coordinate System WKT"),
+        data,
+    }
+}
+
+/// Builds the GeoKeyDirectoryTag VLR of an EPSG code, whose WKT definition
+/// tells whether it is a projected, geographic or geocentric CRS.
+fn geo_key_directory_vlr(code: u16, wkt: &str) -> las::Vlr {
+    let (model_type, crs_key) = if wkt.starts_with("PROJCS") {
+        (1, PROJECTED_CS_TYPE_GEO_KEY)
+    } else if wkt.starts_with("GEOCCS") {
+        (3, GEOGRAPHIC_TYPE_GEO_KEY)
+    } else {
+        (2, GEOGRAPHIC_TYPE_GEO_KEY)
+    };
+
+    // Header (version 1.1.0, 3 keys), then one entry per key, sorted by key id:
+    // key id, tag location (0 = value stored inline), count, value.
+    let keys: [[u16; 4]; 4] = [
+        [1, 1, 0, 3],
+        [GT_MODEL_TYPE_GEO_KEY, 0, 1, model_type],
+        [GT_RASTER_TYPE_GEO_KEY, 0, 1, 1],
+        [crs_key, 0, 1, code],
+    ];
+
+    las::Vlr {
+        user_id: String::from(PROJECTION_USER_ID),
+        record_id: GEO_KEY_DIRECTORY_RECORD_ID,
+        description: String::from("GeoTIFF GeoKeyDirectoryTag"),
+        data: keys
+            .iter()
+            .flatten()
+            .flat_map(|key| key.to_le_bytes())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_crs() {
+        assert_eq!(Crs::try_from("EPSG:2056").ok(), Some(Crs::Epsg(2056)));
+        assert_eq!(Crs::try_from(" 4326 ").ok(), Some(Crs::Epsg(4326)));
+        assert_eq!(Crs::try_from("E57").ok(), Some(Crs::FromE57));
+        assert_eq!(
+            Crs::try_from("GEOGCS[\"WGS 84\"]").ok(),
+            Some(Crs::Wkt(String::from("GEOGCS[\"WGS 84\"]")))
+        );
+        assert!(Crs::try_from("CH1903+ / LV95").is_err());
+        assert!(Crs::try_from("EPSG:99999").is_err());
+    }
+
+    #[test]
+    fn test_resolve_from_e57() {
+        assert_eq!(
+            Crs::FromE57.resolve(Some("EPSG:2056")),
+            Some(Crs::Epsg(2056))
+        );
+        assert_eq!(Crs::FromE57.resolve(Some("local grid")), None);
+        assert_eq!(Crs::FromE57.resolve(None), None);
+        assert_eq!(Crs::Epsg(4326).resolve(None), Some(Crs::Epsg(4326)));
+    }
+
+    #[test]
+    fn test_epsg_from_wkt() {
+        let wkt = Crs::Epsg(2056).wkt().expect("Missing EPSG:2056 definition");
+        assert_eq!(epsg_from_wkt(&wkt), Some(2056));
+        assert_eq!(
+            epsg_from_wkt("PROJCRS[\"x\",BASEGEOGCRS[\"y\",ID[\"EPSG\",4326]],ID[\"EPSG\",32632]]"),
+            Some(32632)
+        );
+        assert_eq!(
+            epsg_from_wkt("PROJCRS[\"x\",ID[\"EPSG\",2056],\n  VERTICALGRID[\"EPSG\",1234]]"),
+            Some(2056)
+        );
+        assert_eq!(epsg_from_wkt("GEOGCS[\"WGS 84\"]"), None);
+        assert_eq!(
+            epsg_from_wkt("GEOGCS[\"x\",AUTHORITY[\"ESRI\",\"1\"]]"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_add_wkt_vlr_for_las_1_4() {
+        let mut builder = las::Builder::from((1, 4));
+        Crs::Epsg(2056)
+            .add_to(&mut builder)
+            .expect("Failed to add CRS");
+
+        let header = builder.into_header().expect("Invalid header");
+        assert!(header.has_wkt_crs());
+        let wkt = header.get_wkt_crs_bytes().expect("Missing WKT VLR");
+        assert!(wkt.starts_with(b"PROJCS[\"CH1903+ / LV95\""));
+        assert_eq!(wkt.last(), Some(&0));
+    }
+
+    #[test]
+    fn test_add_geotiff_keys_for_las_1_2() {
+        let mut builder = las::Builder::from((1, 2));
+        Crs::Epsg(2056)
+            .add_to(&mut builder)
+            .expect("Failed to add CRS");
+        let header = builder.into_header().expect("Invalid header");
+        assert!(!header.has_wkt_crs());
+        let geotiff = header
+            .get_geotiff_crs()
+            .expect("Invalid GeoTIFF keys")
+            .expect("Missing GeoTIFF keys");
+        assert_eq!(geotiff.entries.len(), 3);
+
+        let mut builder = las::Builder::from((1, 2));
+        assert!(
+            Crs::Wkt(String::from("GEOGCS[\"WGS 84\"]"))
+                .add_to(&mut builder)
+                .is_err()
+        );
+    }
+}
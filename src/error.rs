@@ -2,6 +2,30 @@
 pub enum Error {
     #[error("Invalid LAS version {0}")]
     InvalidLasVersion(String),
+    #[error("Invalid CRS {0}")]
+    InvalidCrs(String),
+    #[error("Invalid scale {0}")]
+    InvalidScale(String),
+    #[error("Invalid offset {0}")]
+    InvalidOffset(String),
+    #[error("Invalid intensity mapping {0}")]
+    InvalidIntensity(String),
+    #[error("Invalid pose {0}")]
+    InvalidPose(String),
+    #[error("Invalid transform: {0}")]
+    InvalidTransform(String),
+    #[error("Invalid direction points policy {0}")]
+    InvalidDirectionPolicy(String),
+    #[error("Invalid points policy {0}")]
+    InvalidPointsPolicy(String),
+    #[error("Invalid point format {0}")]
+    InvalidPointFormat(String),
+    #[error("Invalid stations format {0}")]
+    InvalidStationsFormat(String),
+    #[error("Invalid point source IDs {0}")]
+    InvalidPointSourceIds(String),
+    #[error("Invalid name template {0}")]
+    InvalidNameTemplate(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
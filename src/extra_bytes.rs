@@ -0,0 +1,278 @@
+use crate::get_las_writer::PointAttributes;
+
+/// Size in bytes of an Extra Bytes VLR descriptor.
+const DESCRIPTOR_SIZE: usize = 192;
+/// Extra Bytes data type of signed 32-bit integers.
+const DATA_TYPE_I32: u8 = 6;
+/// Extra Bytes data type of 32-bit floats.
+const DATA_TYPE_F32: u8 = 9;
+/// Extra Bytes options bit telling that the `no_data` value is relevant.
+const OPTION_NO_DATA: u8 = 1;
+
+/// An Extra Bytes dimension written by the converter.
+///
+/// While converting, every point carrying any of them stores all of them in
+/// `extra_bytes`, in the order of [`ExtraDimension::ALL`]; the writers then
+/// keep only the dimensions present in the output (see [`select`]), since the
+/// points of other point clouds may not carry the same ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExtraDimension {
+    Row,
+    Column,
+    NormalX,
+    NormalY,
+    NormalZ,
+}
+
+impl ExtraDimension {
+    pub(crate) const ALL: [ExtraDimension; 5] = [
+        ExtraDimension::Row,
+        ExtraDimension::Column,
+        ExtraDimension::NormalX,
+        ExtraDimension::NormalY,
+        ExtraDimension::NormalZ,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            ExtraDimension::Row => "row",
+            ExtraDimension::Column => "column",
+            ExtraDimension::NormalX => "NormalX",
+            ExtraDimension::NormalY => "NormalY",
+            ExtraDimension::NormalZ => "NormalZ",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ExtraDimension::Row => "E57 row index",
+            ExtraDimension::Column => "E57 column index",
+            ExtraDimension::NormalX => "X component of the normal",
+            ExtraDimension::NormalY => "Y component of the normal",
+            ExtraDimension::NormalZ => "Z component of the normal",
+        }
+    }
+
+    fn data_type(self) -> u8 {
+        match self {
+            ExtraDimension::Row | ExtraDimension::Column => DATA_TYPE_I32,
+            ExtraDimension::NormalX | ExtraDimension::NormalY | ExtraDimension::NormalZ => {
+                DATA_TYPE_F32
+            }
+        }
+    }
+
+    fn size(self) -> usize {
+        4
+    }
+
+    /// The value written for points without this dimension: -1 for indices
+    /// (flagged as `no_data`), and a null normal, which no valid normal is.
+    fn no_data(self) -> [u8; 4] {
+        match self.data_type() {
+            DATA_TYPE_I32 => (-1_i32).to_le_bytes(),
+            _ => 0_f32.to_le_bytes(),
+        }
+    }
+
+    /// Returns the bytes of this dimension for a point.
+    fn encode(self, values: &ExtraValues) -> [u8; 4] {
+        let index = |value: Option<i64>| value.and_then(|value| i32::try_from(value).ok());
+        let normal = |axis: usize| values.normal.map(|normal| normal[axis]);
+
+        match self {
+            ExtraDimension::Row => index(values.row).map(i32::to_le_bytes),
+            ExtraDimension::Column => index(values.column).map(i32::to_le_bytes),
+            ExtraDimension::NormalX => normal(0).map(f32::to_le_bytes),
+            ExtraDimension::NormalY => normal(1).map(f32::to_le_bytes),
+            ExtraDimension::NormalZ => normal(2).map(f32::to_le_bytes),
+        }
+        .unwrap_or_else(|| self.no_data())
+    }
+
+    fn is_present(self, attributes: &PointAttributes) -> bool {
+        match self {
+            ExtraDimension::Row | ExtraDimension::Column => attributes.has_row_column,
+            ExtraDimension::NormalX | ExtraDimension::NormalY | ExtraDimension::NormalZ => {
+                attributes.has_normals
+            }
+        }
+    }
+
+    /// Offset of this dimension in the full layout used while converting.
+    fn full_offset(self) -> usize {
+        ExtraDimension::ALL
+            .iter()
+            .take_while(|dimension| **dimension != self)
+            .map(|dimension| dimension.size())
+            .sum()
+    }
+
+    /// Builds the Extra Bytes VLR descriptor of this dimension.
+    fn descriptor(self) -> Vec<u8> {
+        let mut descriptor = vec![0; DESCRIPTOR_SIZE];
+        descriptor[2] = self.data_type();
+        copy_str(&mut descriptor[4..36], self.name());
+        if self.data_type() == DATA_TYPE_I32 {
+            descriptor[3] = OPTION_NO_DATA;
+            descriptor[40..48].copy_from_slice(&(-1_i64).to_le_bytes());
+        }
+        copy_str(&mut descriptor[160..192], self.description());
+        descriptor
+    }
+}
+
+/// The values of the Extra Bytes dimensions of a single point.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct ExtraValues {
+    pub(crate) row: Option<i64>,
+    pub(crate) column: Option<i64>,
+    pub(crate) normal: Option<[f32; 3]>,
+}
+
+impl ExtraValues {
+    /// Encodes the values in the full layout of [`ExtraDimension::ALL`], or as
+    /// no bytes at all if the point has none of them.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        if *self == ExtraValues::default() {
+            return Vec::new();
+        }
+
+        ExtraDimension::ALL
+            .iter()
+            .flat_map(|dimension| dimension.encode(self))
+            .collect()
+    }
+}
+
+/// Returns the Extra Bytes dimensions of the output, given the attributes of
+/// all its points.
+pub(crate) fn dimensions(attributes: &PointAttributes) -> Vec<ExtraDimension> {
+    ExtraDimension::ALL
+        .into_iter()
+        .filter(|dimension| dimension.is_present(attributes))
+        .collect()
+}
+
+/// Returns the number of extra bytes per point of `dimensions`.
+pub(crate) fn size(dimensions: &[ExtraDimension]) -> u16 {
+    dimensions
+        .iter()
+        .map(|dimension| dimension.size() as u16)
+        .sum()
+}
+
+/// Returns the Extra Bytes VLR describing `dimensions`, if any.
+pub(crate) fn vlr(dimensions: &[ExtraDimension]) -> Option<las::Vlr> {
+    if dimensions.is_empty() {
+        return None;
+    }
+
+    Some(las::Vlr {
+        user_id: String::from("LASF_Spec"),
+        record_id: 4,
+        description: String::from("Extra Bytes"),
+        data: dimensions
+            .iter()
+            .flat_map(|dimension| dimension.descriptor())
+            .collect(),
+    })
+}
+
+/// Rewrites the extra bytes of a point from the full layout used while
+/// converting to the layout of `dimensions`, filling in the `no_data` value of
+/// the dimensions the point does not carry.
+pub(crate) fn select(point: &mut las::Point, dimensions: &[ExtraDimension]) {
+    let full = std::mem::take(&mut point.extra_bytes);
+
+    for dimension in dimensions {
+        let offset = dimension.full_offset();
+        match full.get(offset..offset + dimension.size()) {
+            Some(bytes) => point.extra_bytes.extend_from_slice(bytes),
+            None => point.extra_bytes.extend_from_slice(&dimension.no_data()),
+        }
+    }
+}
+
+/// Copies `value` into a null padded fixed-size string field, truncating it.
+fn copy_str(field: &mut [u8], value: &str) {
+    let length = value.len().min(field.len() - 1);
+    field[..length].copy_from_slice(&value.as_bytes()[..length]);
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_and_select() {
+        let values = ExtraValues {
+            row: Some(3),
+            column: Some(7),
+            ..Default::default()
+        };
+        let mut point = las::Point {
+            extra_bytes: values.encode(),
+            ..Default::default()
+        };
+        select(&mut point, &[ExtraDimension::Column]);
+        assert_eq!(point.extra_bytes, 7_i32.to_le_bytes());
+
+        // Points of point clouds without row/column get the no data value.
+        let mut point = las::Point::default();
+        select(&mut point, &[ExtraDimension::Row, ExtraDimension::Column]);
+        assert_eq!(point.extra_bytes, [255; 8]);
+    }
+
+    #[test]
+    fn test_encode_normal() {
+        let values = ExtraValues {
+            normal: Some([0.0, 0.6, 0.8]),
+            ..Default::default()
+        };
+        let mut point = las::Point {
+            extra_bytes: values.encode(),
+            ..Default::default()
+        };
+        select(
+            &mut point,
+            &[
+                ExtraDimension::Row,
+                ExtraDimension::NormalY,
+                ExtraDimension::NormalZ,
+            ],
+        );
+
+        let mut expected = (-1_i32).to_le_bytes().to_vec();
+        expected.extend_from_slice(&0.6_f32.to_le_bytes());
+        expected.extend_from_slice(&0.8_f32.to_le_bytes());
+        assert_eq!(point.extra_bytes, expected);
+    }
+
+    #[test]
+    fn test_encode_without_values() {
+        assert!(ExtraValues::default().encode().is_empty());
+    }
+
+    #[test]
+    fn test_vlr_descriptors() {
+        assert!(vlr(&[]).is_none());
+
+        let attributes = PointAttributes {
+            has_row_column: true,
+            ..Default::default()
+        };
+        let dimensions = dimensions(&attributes);
+        assert_eq!(size(&dimensions), 8);
+
+        let vlr = vlr(&dimensions).expect("Expected an Extra Bytes VLR");
+        assert_eq!(vlr.data.len(), 2 * DESCRIPTOR_SIZE);
+        assert_eq!(vlr.data[2], DATA_TYPE_I32);
+        assert_eq!(&vlr.data[4..8], b"row\0");
+        assert_eq!(
+            &vlr.data[DESCRIPTOR_SIZE + 4..DESCRIPTOR_SIZE + 11],
+            b"column\0"
+        );
+    }
+}
@@ -4,11 +4,35 @@ use anyhow::{Context, Result};
 use las::Vector;
 use uuid::Uuid;
 
-use crate::LasVersion;
+use crate::header_metadata::HeaderMetadata;
+use crate::{ConvertOptions, Offset, Scale, extra_bytes};
 
 // Shared constants for scale calculations
 const MIN_SCALE: f64 = 0.001;
 const QUANTUM: f64 = 1e-4; // 0.0001 - the quantization step for scale values
+const MIN_LOSSLESS_EXPONENT: f64 = -9.0; // 1 nm - the finest lossless scale
+
+/// Difference between standard GPS time and adjusted standard GPS time.
+pub(crate) const ADJUSTED_GPS_TIME_OFFSET: f64 = 1e9;
+
+/// The optional LAS point attributes carried by the converted points, which
+/// decide the LAS point format (see [`crate::PointFormat`]).
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct PointAttributes {
+    pub(crate) has_color: bool,
+    pub(crate) has_gps_time: bool,
+    pub(crate) has_row_column: bool,
+    pub(crate) has_normals: bool,
+}
+
+impl PointAttributes {
+    pub(crate) fn merge(&mut self, other: &PointAttributes) {
+        self.has_color |= other.has_color;
+        self.has_gps_time |= other.has_gps_time;
+        self.has_row_column |= other.has_row_column;
+        self.has_normals |= other.has_normals;
+    }
+}
 
 /// Per-axis min/max bounds of the converted LAS points.
 ///
@@ -59,12 +83,12 @@ impl PointBounds {
     }
 
     /// Returns true if no point was ever added to these bounds.
-    fn is_empty(&self) -> bool {
+    pub(crate) fn is_empty(&self) -> bool {
         self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
     }
 }
 
-fn find_smallest_scale(x: f64) -> f64 {
+pub(crate) fn find_smallest_scale(x: f64) -> f64 {
     // Early return for small values that work with the minimum scale
     if x.abs() <= f64::from(i32::MAX) * MIN_SCALE {
         return MIN_SCALE;
@@ -83,50 +107,96 @@ fn find_smallest_scale(x: f64) -> f64 {
     scale.max(MIN_SCALE)
 }
 
-/// Computes the LAS transform for one axis: the offset is the bounds midpoint
-/// (rounded to whole meters for readable headers) and the scale is the
-/// smallest quantized scale such that every value on the axis fits in i32
-/// once the offset is subtracted.
-fn axis_transform(min: f64, max: f64) -> las::Transform {
-    let offset = ((min + max) / 2.0).round();
+/// Returns the smallest power of ten such that `x` fits in i32 once divided by it.
+fn lossless_scale(x: f64) -> f64 {
+    let exponent = (x.abs() / f64::from(i32::MAX))
+        .log10()
+        .ceil()
+        .max(MIN_LOSSLESS_EXPONENT);
+    let scale = 10_f64.powi(exponent as i32);
+
+    // Guard against the rounding of `powi` at the boundary.
+    if x.abs() / scale > f64::from(i32::MAX) {
+        scale * 10.0
+    } else {
+        scale
+    }
+}
+
+/// Computes the LAS transform for one axis.
+///
+/// By default, the offset is the bounds midpoint (rounded to whole meters for
+/// readable headers) and the scale is the smallest quantized scale such that
+/// every value on the axis fits in i32 once the offset is subtracted. Fixed
+/// scales that cannot fit the bounds are rejected.
+fn axis_transform(min: f64, max: f64, scale: Scale, offset: Offset) -> Result<las::Transform> {
+    let offset = match offset {
+        Offset::Auto => ((min + max) / 2.0).round(),
+        Offset::Fixed(offset) => offset,
+    };
 
-    // Largest distance from the (rounded) offset; the scale must fit this.
+    // Largest distance from the offset; the scale must fit this.
     let half_extent = (max - offset).abs().max((min - offset).abs());
-    let scale = find_smallest_scale(half_extent);
+    let scale = match scale {
+        Scale::Auto => {
+            let scale = find_smallest_scale(half_extent);
+            if scale > MIN_SCALE {
+                eprintln!(
+                    "Warning: coordinates up to {half_extent} from the offset {offset} do not fit in i32 with a scale of {MIN_SCALE}, using a scale of {scale} instead"
+                );
+            }
+            scale
+        }
+        Scale::Lossless => lossless_scale(half_extent),
+        Scale::Fixed(scale) => {
+            if (half_extent / scale).round() > f64::from(i32::MAX) {
+                return Err(anyhow::anyhow!(
+                    "Coordinates up to {half_extent} from the offset {offset} do not fit in i32 with a scale of {scale}; use a scale of at least {}",
+                    find_smallest_scale(half_extent)
+                ));
+            }
+            scale
+        }
+    };
 
-    las::Transform { scale, offset }
+    Ok(las::Transform { scale, offset })
 }
 
-pub(crate) fn get_las_writer(
-    guid: Option<String>,
-    output_path: PathBuf,
-    bounds: PointBounds,
-    has_color: bool,
-    las_version: &LasVersion,
-) -> Result<las::Writer<BufWriter<File>>> {
-    let mut builder = las::Builder::from(las_version);
-    builder.point_format.has_color = has_color;
-    builder.generating_software = String::from("e57_to_las");
-
-    builder.transforms = if bounds.is_empty() {
-        // No valid points: fall back to the legacy default transform.
-        let transform = las::Transform {
-            scale: MIN_SCALE,
-            offset: 0.0,
-        };
-        Vector {
-            x: transform,
-            y: transform,
-            z: transform,
-        }
+/// Computes the per-axis LAS transforms (offset and scale) fitting the bounds.
+pub(crate) fn las_transforms(
+    bounds: &PointBounds,
+    scale: &[Scale; 3],
+    offset: &[Offset; 3],
+) -> Result<Vector<las::Transform>> {
+    // Without valid points, the automatic transform falls back to the legacy
+    // default: a scale of MIN_SCALE and an offset of 0.
+    let (min, max) = if bounds.is_empty() {
+        (Vector::default(), Vector::default())
     } else {
-        Vector {
-            x: axis_transform(bounds.min.x, bounds.max.x),
-            y: axis_transform(bounds.min.y, bounds.max.y),
-            z: axis_transform(bounds.min.z, bounds.max.z),
-        }
+        (bounds.min, bounds.max)
     };
-    builder.guid = match guid {
+
+    Ok(Vector {
+        x: axis_transform(min.x, max.x, scale[0], offset[0]).context("Invalid x transform: ")?,
+        y: axis_transform(min.y, max.y, scale[1], offset[1]).context("Invalid y transform: ")?,
+        z: axis_transform(min.z, max.z, scale[2], offset[2]).context("Invalid z transform: ")?,
+    })
+}
+
+/// Returns the GPS time type of the output header: adjusted standard GPS time
+/// when the timestamps are offset by the acquisition start date, GPS week time
+/// (the LAS default) otherwise.
+pub(crate) fn gps_time_type(options: &ConvertOptions) -> las::GpsTimeType {
+    if options.adjusted_gps_time {
+        las::GpsTimeType::Standard
+    } else {
+        las::GpsTimeType::Week
+    }
+}
+
+/// Parses an E57 guid as the LAS header guid, falling back to a random one.
+pub(crate) fn las_guid(guid: Option<String>) -> Uuid {
+    match guid {
         Some(guid) => Uuid::parse_str(&guid.replace("_", "-")).unwrap_or_else(|_| {
             let fallback = Uuid::new_v4();
             eprintln!(
@@ -135,16 +205,53 @@ pub(crate) fn get_las_writer(
             fallback
         }),
         None => Uuid::new_v4(),
-    };
+    }
+}
+
+pub(crate) fn get_las_writer(
+    metadata: &HeaderMetadata,
+    output_path: PathBuf,
+    bounds: PointBounds,
+    attributes: PointAttributes,
+    options: &ConvertOptions,
+) -> Result<las::Writer<BufWriter<File>>> {
+    if options.compress && !cfg!(feature = "laz") {
+        return Err(anyhow::anyhow!(
+            "LAZ output requires e57-to-las to be built with the `laz` feature"
+        ));
+    }
+
+    let mut builder = las::Builder::from(&options.las_version);
+    builder.point_format = options
+        .point_format
+        .resolve(builder.version, false, &attributes)?;
+    builder.gps_time_type = gps_time_type(options);
+
+    let extra_dimensions = extra_bytes::dimensions(&attributes);
+    builder.point_format.extra_bytes = extra_bytes::size(&extra_dimensions);
+    builder.vlrs.extend(extra_bytes::vlr(&extra_dimensions));
+    builder.point_format.is_compressed = options.compress;
+    builder.generating_software = String::from("e57_to_las");
+
+    builder.transforms = las_transforms(&bounds, &options.scale, &options.offset)?;
+    metadata.add_to(&mut builder);
+
+    if let Some(crs) = &options.crs {
+        crs.add_to(&mut builder)
+            .context("Unable to write the CRS: ")?;
+    }
 
     let header = builder.into_header().context("Error encountered: ")?;
 
-    let writer = las::Writer::from_path(&output_path, header).context("Error encountered: ")?;
+    let file = File::create(&output_path)
+        .with_context(|| format!("Unable to create {}", output_path.display()))?;
+    let writer = las::Writer::new(BufWriter::new(file), header).context("Error encountered: ")?;
 
     Ok(writer)
 }
 
 #[cfg(test)]
+#[allow(clippy::expect_used)]
 mod tests {
     use super::*;
 
@@ -243,7 +350,8 @@ mod tests {
     #[test]
     fn test_axis_transform_small_extent() {
         // Small extents around the origin keep the minimum scale
-        let transform = axis_transform(-10.0, 10.0);
+        let transform = axis_transform(-10.0, 10.0, Scale::Auto, Offset::Auto)
+            .expect("Failed to compute transform");
         assert_eq!(transform.scale, MIN_SCALE);
         assert_eq!(transform.offset, 0.0);
         assert_fits(&transform, -10.0, 10.0);
@@ -255,7 +363,8 @@ mod tests {
         // With the legacy offset = 0 this required scale ~ 0.0024 (2.4 mm
         // quantization). With the midpoint offset the minimum scale fits.
         let (min, max) = (5_000_000.0, 5_000_100.0);
-        let transform = axis_transform(min, max);
+        let transform = axis_transform(min, max, Scale::Auto, Offset::Auto)
+            .expect("Failed to compute transform");
         assert_eq!(transform.scale, MIN_SCALE);
         assert_eq!(transform.offset, 5_000_050.0);
         assert_fits(&transform, min, max);
@@ -265,7 +374,8 @@ mod tests {
     fn test_axis_transform_large_extent() {
         // Extents too large for the minimum scale still fit in i32
         let (min, max) = (-1e10, 1e10);
-        let transform = axis_transform(min, max);
+        let transform = axis_transform(min, max, Scale::Auto, Offset::Auto)
+            .expect("Failed to compute transform");
         assert!(transform.scale > MIN_SCALE);
         assert_fits(&transform, min, max);
 
@@ -278,8 +388,59 @@ mod tests {
     fn test_axis_transform_asymmetric_extent() {
         // Offset rounding must not break the fit for asymmetric bounds
         let (min, max) = (-3.5e6, 9.7e6 + 0.4321);
-        let transform = axis_transform(min, max);
+        let transform = axis_transform(min, max, Scale::Auto, Offset::Auto)
+            .expect("Failed to compute transform");
+        assert_fits(&transform, min, max);
+    }
+
+    #[test]
+    fn test_axis_transform_fixed_scale_and_offset() {
+        let (min, max) = (2_600_010.0, 2_600_020.0);
+        let transform = axis_transform(min, max, Scale::Fixed(0.0001), Offset::Fixed(2_600_000.0))
+            .expect("Failed to compute transform");
+        assert_eq!(transform.scale, 0.0001);
+        assert_eq!(transform.offset, 2_600_000.0);
         assert_fits(&transform, min, max);
+
+        // 0.1 mm cannot reach 2,600 km from a zero offset in i32
+        assert!(axis_transform(min, max, Scale::Fixed(0.0001), Offset::Fixed(0.0)).is_err());
+    }
+
+    #[test]
+    fn test_axis_transform_lossless_scale() {
+        let (min, max) = (-10.0, 10.0);
+        let transform = axis_transform(min, max, Scale::Lossless, Offset::Auto)
+            .expect("Failed to compute transform");
+        assert_eq!(transform.scale, 1e-8);
+        assert_fits(&transform, min, max);
+
+        let (min, max) = (5_000_000.0, 5_000_100.0);
+        let transform = axis_transform(min, max, Scale::Lossless, Offset::Fixed(0.0))
+            .expect("Failed to compute transform");
+        assert_eq!(transform.scale, 0.01);
+        assert_fits(&transform, min, max);
+    }
+
+    #[test]
+    fn test_las_transforms_per_axis() {
+        let mut bounds = PointBounds::default();
+        bounds.update(&las::Point {
+            x: 100.0,
+            y: 200.0,
+            z: 300.0,
+            ..Default::default()
+        });
+        let transforms = las_transforms(
+            &bounds,
+            &[Scale::Fixed(0.01), Scale::Auto, Scale::Auto],
+            &[Offset::Auto, Offset::Fixed(0.0), Offset::Auto],
+        )
+        .expect("Failed to compute transforms");
+        assert_eq!(transforms.x.scale, 0.01);
+        assert_eq!(transforms.x.offset, 100.0);
+        assert_eq!(transforms.y.scale, MIN_SCALE);
+        assert_eq!(transforms.y.offset, 0.0);
+        assert_eq!(transforms.z.offset, 300.0);
     }
 
     #[test]
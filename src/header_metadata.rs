@@ -0,0 +1,415 @@
+use chrono::NaiveDate;
+use e57::PointCloud;
+use serde::Serialize;
+
+use crate::get_las_writer::las_guid;
+
+/// User id of the VLR holding the E57 metadata.
+const METADATA_USER_ID: &str = "e57_to_las";
+/// Record id of the VLR holding the E57 metadata.
+const METADATA_RECORD_ID: u16 = 1;
+/// Record id of the VLR mapping the point source IDs to the point clouds.
+const POINT_SOURCES_RECORD_ID: u16 = 2;
+/// Size in bytes of the LAS system identifier field.
+const SYSTEM_IDENTIFIER_SIZE: usize = 32;
+/// Seconds per day.
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// The E57 metadata written in the LAS header, so that the provenance of the
+/// points survives the conversion.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HeaderMetadata {
+    /// The E57 guid of the converted file or point cloud.
+    guid: Option<String>,
+    /// The E57 creation date, or the earliest acquisition start.
+    date: Option<NaiveDate>,
+    /// The scanner vendor and model, or `MERGE` when the points come from
+    /// several scanners.
+    system_identifier: Option<String>,
+    /// The E57 metadata as JSON.
+    json: Vec<u8>,
+    /// The point clouds behind the point source IDs of a merged file, as JSON.
+    point_sources: Option<Vec<u8>>,
+}
+
+/// The E57 file metadata stored as JSON in the metadata VLR.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileMetadata<'a> {
+    guid: &'a str,
+    /// GPS time (seconds since 1980-01-06) of the E57 file creation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creation_date_time: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    library_version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    coordinate_metadata: Option<&'a str>,
+    scans: Vec<ScanMetadata<'a>>,
+}
+
+/// The metadata of several merged E57 files stored as JSON in the metadata
+/// VLR.
+#[derive(Debug, Serialize)]
+struct MergedMetadata<'a> {
+    files: Vec<FileMetadata<'a>>,
+}
+
+/// A point source ID and the point cloud whose points carry it, stored as a
+/// JSON array in the point sources VLR.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PointSource<'a> {
+    point_source_id: u16,
+    /// Index of the point cloud, counted across all the merged files.
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guid: Option<&'a str>,
+}
+
+/// The metadata of an E57 point cloud stored as JSON in the metadata VLR.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanMetadata<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guid: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_vendor: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_serial_number: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_hardware_version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_software_version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_firmware_version: Option<&'a str>,
+    /// GPS time of the acquisition start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquisition_start: Option<f64>,
+    /// GPS time of the acquisition end.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquisition_end: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_humidity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    atmospheric_pressure: Option<f64>,
+}
+
+impl<'a> From<&'a PointCloud> for ScanMetadata<'a> {
+    fn from(pointcloud: &'a PointCloud) -> Self {
+        Self {
+            guid: pointcloud.guid.as_deref(),
+            name: pointcloud.name.as_deref(),
+            description: pointcloud.description.as_deref(),
+            sensor_vendor: pointcloud.sensor_vendor.as_deref(),
+            sensor_model: pointcloud.sensor_model.as_deref(),
+            sensor_serial_number: pointcloud.sensor_serial.as_deref(),
+            sensor_hardware_version: pointcloud.sensor_hw_version.as_deref(),
+            sensor_software_version: pointcloud.sensor_sw_version.as_deref(),
+            sensor_firmware_version: pointcloud.sensor_fw_version.as_deref(),
+            acquisition_start: pointcloud.acquisition_start.as_ref().map(|d| d.gps_time),
+            acquisition_end: pointcloud.acquisition_end.as_ref().map(|d| d.gps_time),
+            temperature: pointcloud.temperature,
+            relative_humidity: pointcloud.humidity,
+            atmospheric_pressure: pointcloud.atmospheric_pressure,
+        }
+    }
+}
+
+impl<'a> FileMetadata<'a> {
+    fn new<R: std::io::Read + std::io::Seek>(
+        e57_reader: &'a e57::E57Reader<R>,
+        pointclouds: &[&'a PointCloud],
+    ) -> Self {
+        Self {
+            guid: e57_reader.guid(),
+            creation_date_time: e57_reader.creation().map(|date| date.gps_time),
+            library_version: e57_reader.library_version(),
+            coordinate_metadata: e57_reader.coordinate_metadata(),
+            scans: pointclouds
+                .iter()
+                .map(|pointcloud| ScanMetadata::from(*pointcloud))
+                .collect(),
+        }
+    }
+}
+
+impl HeaderMetadata {
+    /// Gathers the metadata of the `pointclouds` of an E57 file written to a
+    /// single LAS file whose guid is `guid`.
+    pub(crate) fn new<R: std::io::Read + std::io::Seek>(
+        e57_reader: &e57::E57Reader<R>,
+        guid: Option<String>,
+        pointclouds: &[&PointCloud],
+    ) -> anyhow::Result<Self> {
+        let metadata = FileMetadata::new(e57_reader, pointclouds);
+        Ok(Self {
+            guid,
+            date: date(&[e57_reader], pointclouds),
+            system_identifier: system_identifier(pointclouds),
+            json: serde_json::to_vec(&metadata)?,
+            point_sources: None,
+        })
+    }
+
+    /// Gathers the metadata of the point clouds of several E57 files merged
+    /// in a single LAS file, which gets a new guid. The JSON metadata lists
+    /// the files under `files`.
+    pub(crate) fn merged<R: std::io::Read + std::io::Seek>(
+        files: &[(&e57::E57Reader<R>, Vec<&PointCloud>)],
+    ) -> anyhow::Result<Self> {
+        let e57_readers = files.iter().map(|(reader, _)| *reader).collect::<Vec<_>>();
+        let pointclouds = files
+            .iter()
+            .flat_map(|(_, pointclouds)| pointclouds.iter().copied())
+            .collect::<Vec<_>>();
+        let metadata = MergedMetadata {
+            files: files
+                .iter()
+                .map(|(reader, pointclouds)| FileMetadata::new(reader, pointclouds))
+                .collect(),
+        };
+
+        Ok(Self {
+            guid: None,
+            date: date(&e57_readers, &pointclouds),
+            system_identifier: system_identifier(&pointclouds),
+            json: serde_json::to_vec(&metadata)?,
+            point_sources: None,
+        })
+    }
+
+    /// Records the point source ID given to the points of each point cloud,
+    /// written as a JSON VLR mapping the IDs to the names and GUIDs of the
+    /// point clouds.
+    pub(crate) fn set_point_sources(
+        &mut self,
+        pointclouds: &[&PointCloud],
+        ids: &[u16],
+    ) -> anyhow::Result<()> {
+        let point_sources = pointclouds
+            .iter()
+            .zip(ids)
+            .enumerate()
+            .map(|(index, (pointcloud, id))| PointSource {
+                point_source_id: *id,
+                index,
+                name: pointcloud.name.as_deref(),
+                guid: pointcloud.guid.as_deref(),
+            })
+            .collect::<Vec<_>>();
+        self.point_sources = Some(serde_json::to_vec(&point_sources)?);
+        Ok(())
+    }
+
+    /// Writes the metadata in the header of `builder`: guid, file creation
+    /// date, system identifier, the JSON metadata VLR and, for merged files,
+    /// the point sources VLR.
+    pub(crate) fn add_to(&self, builder: &mut las::Builder) {
+        builder.guid = las_guid(self.guid.clone());
+        if let Some(date) = self.date {
+            builder.date = Some(date);
+        }
+        if let Some(system_identifier) = &self.system_identifier {
+            builder.system_identifier = system_identifier.clone();
+        }
+
+        push_vlr(
+            builder,
+            METADATA_RECORD_ID,
+            "E57 metadata (JSON)",
+            self.json.clone(),
+        );
+        if let Some(point_sources) = &self.point_sources {
+            push_vlr(
+                builder,
+                POINT_SOURCES_RECORD_ID,
+                "Point source IDs (JSON)",
+                point_sources.clone(),
+            );
+        }
+    }
+}
+
+/// Adds an `e57_to_las` VLR to `builder`, which becomes an extended VLR in
+/// LAS 1.4 files when too large for a regular one.
+fn push_vlr(builder: &mut las::Builder, record_id: u16, description: &str, data: Vec<u8>) {
+    let size = data.len();
+    let vlr = las::Vlr {
+        user_id: String::from(METADATA_USER_ID),
+        record_id,
+        description: String::from(description),
+        data,
+    };
+    if size <= usize::from(u16::MAX) {
+        builder.vlrs.push(vlr);
+    } else if builder.version >= las::Version::new(1, 4) {
+        builder.evlrs.push(vlr);
+    } else {
+        eprintln!(
+            "Warning: the {description:?} VLR ({size} bytes) is too large for LAS {}, use LAS 1.4 to keep it",
+            builder.version
+        );
+    }
+}
+
+/// Returns the earliest E57 creation date, or the earliest acquisition start
+/// when no file has any.
+fn date<R: std::io::Read + std::io::Seek>(
+    e57_readers: &[&e57::E57Reader<R>],
+    pointclouds: &[&PointCloud],
+) -> Option<NaiveDate> {
+    let creation = e57_readers
+        .iter()
+        .filter_map(|reader| reader.creation())
+        .map(|date| date.gps_time)
+        .reduce(f64::min);
+    let earliest_acquisition = pointclouds
+        .iter()
+        .filter_map(|pointcloud| pointcloud.acquisition_start.as_ref())
+        .map(|date| date.gps_time)
+        .reduce(f64::min);
+
+    creation.or(earliest_acquisition).and_then(gps_date)
+}
+
+/// Returns the UTC date of a GPS time, ignoring leap seconds.
+pub(crate) fn gps_date(gps_time: f64) -> Option<NaiveDate> {
+    let days = (gps_time / SECONDS_PER_DAY).floor();
+    if !days.is_finite() {
+        return None;
+    }
+
+    NaiveDate::from_ymd_opt(1980, 1, 6)?
+        .checked_add_signed(chrono::Duration::try_days(days as i64)?)
+}
+
+/// Returns the scanner vendor and model shared by all the point clouds, `MERGE`
+/// (as the LAS specification suggests) if they come from several scanners, or
+/// `None` if none is known.
+fn system_identifier(pointclouds: &[&PointCloud]) -> Option<String> {
+    let mut scanners = pointclouds.iter().filter_map(|pointcloud| {
+        let scanner = [&pointcloud.sensor_vendor, &pointcloud.sensor_model]
+            .into_iter()
+            .flatten()
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!scanner.is_empty()).then_some(scanner)
+    });
+
+    let first = scanners.next()?;
+    if scanners.any(|scanner| scanner != first) {
+        return Some(String::from("MERGE"));
+    }
+
+    Some(truncate(first, SYSTEM_IDENTIFIER_SIZE))
+}
+
+/// Truncates a string to at most `size` bytes, on a character boundary.
+fn truncate(mut value: String, size: usize) -> String {
+    if value.len() > size {
+        let end = (0..=size)
+            .rev()
+            .find(|index| value.is_char_boundary(*index))
+            .unwrap_or(0);
+        value.truncate(end);
+    }
+    value
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn pointcloud(vendor: &str, model: &str) -> PointCloud {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.sensor_vendor = Some(String::from(vendor));
+        pointcloud.sensor_model = Some(String::from(model));
+        pointcloud
+    }
+
+    #[test]
+    fn test_gps_date() {
+        assert_eq!(gps_date(0.0), NaiveDate::from_ymd_opt(1980, 1, 6));
+        // 2021-03-12T10:00:00 UTC
+        assert_eq!(
+            gps_date(1_299_578_400.0),
+            NaiveDate::from_ymd_opt(2021, 3, 12)
+        );
+        assert_eq!(gps_date(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_system_identifier() {
+        let leica = pointcloud("Leica", "RTC360");
+        let faro = pointcloud("FARO", "Focus S 350");
+        let unknown = PointCloud::default();
+
+        assert_eq!(
+            system_identifier(&[&unknown, &leica, &leica]),
+            Some(String::from("Leica RTC360"))
+        );
+        assert_eq!(
+            system_identifier(&[&leica, &faro]),
+            Some(String::from("MERGE"))
+        );
+        assert_eq!(system_identifier(&[&unknown]), None);
+
+        let long = pointcloud("Scanner Manufacturing Company", "Model 1000");
+        assert_eq!(
+            system_identifier(&[&long]).map(|identifier| identifier.len()),
+            Some(SYSTEM_IDENTIFIER_SIZE)
+        );
+    }
+
+    #[test]
+    fn test_point_sources_vlr() {
+        let mut metadata = HeaderMetadata {
+            guid: None,
+            date: None,
+            system_identifier: None,
+            json: b"{}".to_vec(),
+            point_sources: None,
+        };
+        let mut north = pointcloud("Leica", "RTC360");
+        north.name = Some(String::from("North"));
+        let south = PointCloud::default();
+        metadata
+            .set_point_sources(&[&north, &south], &[10, 20])
+            .expect("Failed to serialize point sources");
+
+        let mut builder = las::Builder::default();
+        metadata.add_to(&mut builder);
+
+        let vlr = builder
+            .vlrs
+            .iter()
+            .find(|vlr| vlr.record_id == POINT_SOURCES_RECORD_ID)
+            .expect("Missing point sources VLR");
+        assert_eq!(
+            String::from_utf8_lossy(&vlr.data),
+            r#"[{"pointSourceId":10,"index":0,"name":"North"},{"pointSourceId":20,"index":1}]"#
+        );
+    }
+
+    #[test]
+    fn test_truncate_on_char_boundary() {
+        assert_eq!(
+            truncate(String::from("Zoller+Fröhlich"), 13),
+            "Zoller+Fröhl"
+        );
+        assert_eq!(truncate(String::from("short"), 32), "short");
+    }
+}
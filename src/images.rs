@@ -0,0 +1,334 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write as _},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use e57::{E57Reader, Image, ImageBlob, ImageFormat, PointCloud, Projection};
+use serde::Serialize;
+
+use crate::spatial_point::SpatialPoint;
+use crate::{ConvertOptions, Pose};
+
+/// The JSON sidecar written next to every exported image.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageSidecar<'a> {
+    /// File name of the exported image, next to the sidecar.
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guid: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    /// GUID of the point cloud (scan) the image was taken with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pointcloud_guid: Option<&'a str>,
+    /// Index of that point cloud, i.e. of its LAS file and station.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pointcloud_index: Option<usize>,
+    /// GPS time (seconds since 1980-01-06) of the acquisition.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquisition: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_vendor: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_serial_number: Option<&'a str>,
+    /// The E57 pose of the image, in the E57 project frame.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pose: Option<ImagePose>,
+    /// The position of the image in the frame of the converted points, like
+    /// the station positions. Absent with [`Pose::Ignore`], since the points
+    /// are then in the frame of their scanner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<SpatialPoint>,
+    projection: ImageProjection,
+}
+
+#[derive(Debug, Serialize)]
+struct ImagePose {
+    /// Unit quaternion (w, x, y, z) of the rotation from the image frame to the
+    /// project frame.
+    rotation: [f64; 4],
+    translation: SpatialPoint,
+}
+
+/// The projection model of an exported image, as described in the E57
+/// standard.
+#[derive(Debug, Serialize)]
+#[serde(tag = "model", rename_all = "camelCase")]
+enum ImageProjection {
+    #[serde(rename_all = "camelCase")]
+    Pinhole {
+        width: u32,
+        height: u32,
+        focal_length: f64,
+        pixel_width: f64,
+        pixel_height: f64,
+        principal_x: f64,
+        principal_y: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Spherical {
+        width: u32,
+        height: u32,
+        pixel_width: f64,
+        pixel_height: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Cylindrical {
+        width: u32,
+        height: u32,
+        radius: f64,
+        principal_y: f64,
+        pixel_width: f64,
+        pixel_height: f64,
+    },
+    /// An image for visual reference only, which cannot be projected on the
+    /// points.
+    VisualReference { width: u32, height: u32 },
+}
+
+/// Returns the blob of an image with its projection model: its projectable
+/// representation or, if it has none, its visual reference.
+fn image_blob(image: &Image) -> Option<(&ImageBlob, ImageProjection)> {
+    match (&image.projection, &image.visual_reference) {
+        (Some(Projection::Pinhole(pinhole)), _) => {
+            let p = &pinhole.properties;
+            Some((
+                &pinhole.blob,
+                ImageProjection::Pinhole {
+                    width: p.width,
+                    height: p.height,
+                    focal_length: p.focal_length,
+                    pixel_width: p.pixel_width,
+                    pixel_height: p.pixel_height,
+                    principal_x: p.principal_x,
+                    principal_y: p.principal_y,
+                },
+            ))
+        }
+        (Some(Projection::Spherical(spherical)), _) => {
+            let p = &spherical.properties;
+            Some((
+                &spherical.blob,
+                ImageProjection::Spherical {
+                    width: p.width,
+                    height: p.height,
+                    pixel_width: p.pixel_width,
+                    pixel_height: p.pixel_height,
+                },
+            ))
+        }
+        (Some(Projection::Cylindrical(cylindrical)), _) => {
+            let p = &cylindrical.properties;
+            Some((
+                &cylindrical.blob,
+                ImageProjection::Cylindrical {
+                    width: p.width,
+                    height: p.height,
+                    radius: p.radius,
+                    principal_y: p.principal_y,
+                    pixel_width: p.pixel_width,
+                    pixel_height: p.pixel_height,
+                },
+            ))
+        }
+        (None, Some(reference)) => Some((
+            &reference.blob,
+            ImageProjection::VisualReference {
+                width: reference.properties.width,
+                height: reference.properties.height,
+            },
+        )),
+        (None, None) => None,
+    }
+}
+
+/// Builds the sidecar of the image exported as `file`.
+fn sidecar<'a>(
+    image: &'a Image,
+    file: String,
+    projection: ImageProjection,
+    pointclouds: &[PointCloud],
+    options: &ConvertOptions,
+) -> ImageSidecar<'a> {
+    let pointcloud_index = image.pointcloud_guid.as_ref().and_then(|guid| {
+        pointclouds
+            .iter()
+            .position(|pointcloud| pointcloud.guid.as_ref() == Some(guid))
+    });
+
+    let position = match (options.pose, &image.transform) {
+        (Pose::Apply, Some(t)) => {
+            let mut position = [t.translation.x, t.translation.y, t.translation.z];
+            if let Some(transform) = &options.transform {
+                position = transform.apply(position);
+            }
+            let [x, y, z] = position;
+            Some(SpatialPoint { x, y, z })
+        }
+        _ => None,
+    };
+
+    ImageSidecar {
+        file,
+        guid: image.guid.as_deref(),
+        name: image.name.as_deref(),
+        description: image.description.as_deref(),
+        pointcloud_guid: image.pointcloud_guid.as_deref(),
+        pointcloud_index,
+        acquisition: image.acquisition.as_ref().map(|date| date.gps_time),
+        sensor_vendor: image.sensor_vendor.as_deref(),
+        sensor_model: image.sensor_model.as_deref(),
+        sensor_serial_number: image.sensor_serial.as_deref(),
+        pose: image.transform.as_ref().map(|t| ImagePose {
+            rotation: [t.rotation.w, t.rotation.x, t.rotation.y, t.rotation.z],
+            translation: SpatialPoint {
+                x: t.translation.x,
+                y: t.translation.y,
+                z: t.translation.z,
+            },
+        }),
+        position,
+        projection,
+    }
+}
+
+/// Exports the `images2D` of the E57 file at `input_path` to
+/// `<output_path>/images/`: every image blob is written as `<index>.jpg` (or
+/// `.png`) next to a `<index>.json` sidecar holding its pose, projection model
+/// and the GUID of its scan. Images without any blob are skipped with a
+/// warning.
+pub(crate) fn save_images(
+    input_path: &Path,
+    output_path: &Path,
+    pointclouds: &[PointCloud],
+    options: &ConvertOptions,
+) -> Result<()> {
+    let mut e57_reader = E57Reader::from_file(input_path).context("Failed to open e57 file: ")?;
+    let images = e57_reader.images();
+    if images.is_empty() {
+        return Ok(());
+    }
+
+    let images_path = output_path.join("images");
+    std::fs::create_dir_all(&images_path).with_context(|| {
+        format!(
+            "Couldn't find or create output dir {}.",
+            images_path.display()
+        )
+    })?;
+
+    for (index, image) in images.iter().enumerate() {
+        let Some((blob, projection)) = image_blob(image) else {
+            eprintln!("Warning: image {index} has no image data, skipping it");
+            continue;
+        };
+
+        println!("Saving image {index}...");
+
+        let extension = match blob.format {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+        };
+        let file = format!("{index}.{extension}");
+
+        let image_file = File::create(images_path.join(&file))
+            .with_context(|| format!("Unable to create image {file}"))?;
+        let mut writer = BufWriter::new(image_file);
+        e57_reader
+            .blob(&blob.data, &mut writer)
+            .with_context(|| format!("Unable to read the data of image {index}"))?;
+        writer.flush()?;
+
+        let sidecar_file = File::create(images_path.join(format!("{index}.json")))?;
+        let mut writer = BufWriter::new(sidecar_file);
+        serde_json::to_writer(
+            &mut writer,
+            &sidecar(image, file, projection, pointclouds, options),
+        )?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{POINTCLOUD_GUID, cartesian_prototype, write_e57_with};
+    use e57::{RecordValue, SphericalImageProperties, Transform, Translation};
+
+    /// A stand-in for the JPEG data of an image.
+    const JPEG_DATA: [u8; 8] = [0xFF, 0xD8, 0xFF, 0xE0, 0, 0, 0xFF, 0xD9];
+
+    fn write_e57_with_image(path: &Path) {
+        write_e57_with(
+            path,
+            cartesian_prototype(),
+            vec![vec![RecordValue::Double(1.0); 3]],
+            |_| {},
+            |writer| {
+                let mut image_writer = writer
+                    .add_image("b0000000-0000-0000-0000-000000000001")
+                    .expect("Failed to add image");
+                image_writer.set_name("pano 01");
+                image_writer.set_pointcloud_guid(POINTCLOUD_GUID);
+                image_writer.set_transform(Transform {
+                    translation: Translation {
+                        x: 10.0,
+                        y: 20.0,
+                        z: 30.0,
+                    },
+                    ..Default::default()
+                });
+                image_writer
+                    .add_spherical(
+                        ImageFormat::Jpeg,
+                        &mut &JPEG_DATA[..],
+                        SphericalImageProperties {
+                            width: 8,
+                            height: 4,
+                            pixel_width: std::f64::consts::PI / 4.0,
+                            pixel_height: std::f64::consts::PI / 4.0,
+                        },
+                        None,
+                    )
+                    .expect("Failed to add spherical image");
+                image_writer.finalize().expect("Failed to finalize image");
+            },
+        );
+    }
+
+    #[test]
+    fn test_save_images() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let input_path = dir.path().join("image.e57");
+        write_e57_with_image(&input_path);
+
+        save_images(&input_path, dir.path(), &[], &ConvertOptions::default())
+            .expect("Failed to save images");
+
+        let image =
+            std::fs::read(dir.path().join("images").join("0.jpg")).expect("Missing exported image");
+        assert_eq!(image, JPEG_DATA);
+
+        let sidecar: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(dir.path().join("images").join("0.json"))
+                .expect("Missing image sidecar"),
+        )
+        .expect("Invalid image sidecar");
+        assert_eq!(sidecar["file"], "0.jpg");
+        assert_eq!(sidecar["pointcloudGuid"], POINTCLOUD_GUID);
+        assert_eq!(sidecar["projection"]["model"], "spherical");
+        assert_eq!(sidecar["projection"]["width"], 8);
+        assert_eq!(sidecar["position"]["x"], 10.0);
+        assert_eq!(sidecar["pose"]["rotation"][0], 1.0);
+    }
+}
@@ -0,0 +1,391 @@
+use std::{fmt, path::Path};
+
+use anyhow::Context;
+use e57::{E57Reader, Image, PointCloud, Projection, Record, RecordName};
+use serde::Serialize;
+
+use crate::header_metadata::gps_date;
+use crate::intensity::intensity_range;
+use crate::raw_fields::has_normals;
+use crate::spatial_point::SpatialPoint;
+
+/// A summary of an E57 file, as printed by the `info` command.
+///
+/// # Example
+/// ```no_run
+/// let info = e57_to_las::inspect_file("path/to/input.e57").expect("Failed to read E57 file");
+/// println!("{info}");
+/// ```
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInfo {
+    pub guid: String,
+    pub format_name: String,
+    /// The E57 format version, e.g. `1.0`.
+    pub version: String,
+    pub library_version: Option<String>,
+    /// The coordinate system of the project frame, e.g. an EPSG code or WKT.
+    pub coordinate_metadata: Option<String>,
+    /// GPS time (seconds since 1980-01-06) of the file creation.
+    pub creation: Option<f64>,
+    pub scans: Vec<ScanInfo>,
+    pub images: Vec<ImageInfo>,
+}
+
+/// A summary of an E57 point cloud.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub guid: Option<String>,
+    pub point_count: u64,
+    /// The point attributes: `cartesian`, `spherical`, `color`, `intensity`,
+    /// `rowColumn`, `returns`, `timestamp`, `normals`, followed by the other
+    /// extension fields as `namespace:name`.
+    pub attributes: Vec<String>,
+    /// The pose of the scanner in the project frame.
+    pub pose: Option<ScanPose>,
+    /// The cartesian bounds of the points, in the frame of the scanner.
+    pub bounds: Option<ScanBounds>,
+    /// The intensity range: the `intensityLimits`, or the bounds of the
+    /// intensity data type when missing.
+    pub intensity_limits: Option<[f64; 2]>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanPose {
+    /// Unit quaternion (w, x, y, z) of the rotation.
+    pub rotation: [f64; 4],
+    pub translation: SpatialPoint,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanBounds {
+    pub min: SpatialPoint,
+    pub max: SpatialPoint,
+}
+
+/// A summary of an E57 image.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub guid: Option<String>,
+    /// GUID of the point cloud the image was taken with.
+    pub pointcloud_guid: Option<String>,
+    /// `pinhole`, `spherical`, `cylindrical` or `visualReference`.
+    pub projection: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// Reads the header and metadata of the E57 file at `path`, without reading
+/// its points.
+pub fn inspect_file(path: impl AsRef<Path>) -> crate::Result<FileInfo> {
+    let path = path.as_ref();
+    let e57_reader = E57Reader::from_file(path)
+        .with_context(|| format!("Failed to open e57 file {}", path.display()))?;
+    let header = e57_reader.header();
+
+    Ok(FileInfo {
+        guid: String::from(e57_reader.guid()),
+        format_name: String::from(e57_reader.format_name()),
+        version: format!("{}.{}", header.major, header.minor),
+        library_version: e57_reader.library_version().map(String::from),
+        coordinate_metadata: e57_reader.coordinate_metadata().map(String::from),
+        creation: e57_reader.creation().map(|date| date.gps_time),
+        scans: e57_reader
+            .pointclouds()
+            .iter()
+            .enumerate()
+            .map(|(index, pointcloud)| scan_info(index, pointcloud))
+            .collect(),
+        images: e57_reader
+            .images()
+            .iter()
+            .enumerate()
+            .map(|(index, image)| image_info(index, image))
+            .collect(),
+    })
+}
+
+fn scan_info(index: usize, pointcloud: &PointCloud) -> ScanInfo {
+    let bounds = pointcloud.cartesian_bounds.as_ref().and_then(|bounds| {
+        Some(ScanBounds {
+            min: SpatialPoint {
+                x: bounds.x_min?,
+                y: bounds.y_min?,
+                z: bounds.z_min?,
+            },
+            max: SpatialPoint {
+                x: bounds.x_max?,
+                y: bounds.y_max?,
+                z: bounds.z_max?,
+            },
+        })
+    });
+
+    ScanInfo {
+        index,
+        name: pointcloud.name.clone(),
+        guid: pointcloud.guid.clone(),
+        point_count: pointcloud.records,
+        attributes: attributes(&pointcloud.prototype),
+        pose: pointcloud.transform.as_ref().map(|t| ScanPose {
+            rotation: [t.rotation.w, t.rotation.x, t.rotation.y, t.rotation.z],
+            translation: SpatialPoint {
+                x: t.translation.x,
+                y: t.translation.y,
+                z: t.translation.z,
+            },
+        }),
+        bounds,
+        intensity_limits: intensity_range(pointcloud).map(|(min, max)| [min, max]),
+    }
+}
+
+/// Summarizes the fields of a prototype into the attributes of the points.
+fn attributes(prototype: &[Record]) -> Vec<String> {
+    let has = |name: RecordName| prototype.iter().any(|record| record.name == name);
+    let normals = has_normals(prototype);
+
+    let mut attributes = [
+        ("cartesian", has(RecordName::CartesianX)),
+        ("spherical", has(RecordName::SphericalRange)),
+        ("color", has(RecordName::ColorRed)),
+        ("intensity", has(RecordName::Intensity)),
+        ("rowColumn", has(RecordName::RowIndex)),
+        ("returns", has(RecordName::ReturnIndex)),
+        ("timestamp", has(RecordName::TimeStamp)),
+        ("normals", normals),
+    ]
+    .into_iter()
+    .filter(|(_, present)| *present)
+    .map(|(name, _)| String::from(name))
+    .collect::<Vec<_>>();
+
+    for record in prototype {
+        if let RecordName::Unknown { namespace, name } = &record.name {
+            if normals && ["normalX", "normalY", "normalZ"].contains(&name.as_str()) {
+                continue;
+            }
+            attributes.push(format!("{namespace}:{name}"));
+        }
+    }
+
+    attributes
+}
+
+fn image_info(index: usize, image: &Image) -> ImageInfo {
+    let representation = match (&image.projection, &image.visual_reference) {
+        (Some(Projection::Pinhole(pinhole)), _) => Some((
+            "pinhole",
+            pinhole.properties.width,
+            pinhole.properties.height,
+        )),
+        (Some(Projection::Spherical(spherical)), _) => Some((
+            "spherical",
+            spherical.properties.width,
+            spherical.properties.height,
+        )),
+        (Some(Projection::Cylindrical(cylindrical)), _) => Some((
+            "cylindrical",
+            cylindrical.properties.width,
+            cylindrical.properties.height,
+        )),
+        (None, Some(reference)) => Some((
+            "visualReference",
+            reference.properties.width,
+            reference.properties.height,
+        )),
+        (None, None) => None,
+    };
+
+    ImageInfo {
+        index,
+        name: image.name.clone(),
+        guid: image.guid.clone(),
+        pointcloud_guid: image.pointcloud_guid.clone(),
+        projection: representation.map(|(projection, _, _)| String::from(projection)),
+        width: representation.map(|(_, width, _)| width),
+        height: representation.map(|(_, _, height)| height),
+    }
+}
+
+/// Formats an optional value, `-` when missing.
+fn or_dash(value: Option<&str>) -> &str {
+    value.unwrap_or("-")
+}
+
+impl fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "GUID:              {}", self.guid)?;
+        writeln!(
+            f,
+            "Format:            {} {}",
+            self.format_name, self.version
+        )?;
+        writeln!(
+            f,
+            "Library version:   {}",
+            or_dash(self.library_version.as_deref())
+        )?;
+        writeln!(
+            f,
+            "Coordinate system: {}",
+            or_dash(self.coordinate_metadata.as_deref())
+        )?;
+        match self.creation {
+            Some(creation) => writeln!(
+                f,
+                "Creation:          {} (GPS time {creation})",
+                gps_date(creation).map_or_else(|| String::from("?"), |date| date.to_string())
+            )?,
+            None => writeln!(f, "Creation:          -")?,
+        }
+
+        writeln!(f, "Scans:             {}", self.scans.len())?;
+        for scan in &self.scans {
+            writeln!(f, "  [{}] {}", scan.index, or_dash(scan.name.as_deref()))?;
+            writeln!(f, "      GUID:       {}", or_dash(scan.guid.as_deref()))?;
+            writeln!(f, "      Points:     {}", scan.point_count)?;
+            writeln!(f, "      Attributes: {}", scan.attributes.join(", "))?;
+            match &scan.pose {
+                Some(pose) => {
+                    let [w, x, y, z] = pose.rotation;
+                    let t = &pose.translation;
+                    writeln!(
+                        f,
+                        "      Pose:       translation ({}, {}, {}), rotation ({w}, {x}, {y}, {z})",
+                        t.x, t.y, t.z
+                    )?;
+                }
+                None => writeln!(f, "      Pose:       -")?,
+            }
+            match &scan.bounds {
+                Some(ScanBounds { min, max }) => writeln!(
+                    f,
+                    "      Bounds:     ({}, {}, {}) to ({}, {}, {})",
+                    min.x, min.y, min.z, max.x, max.y, max.z
+                )?,
+                None => writeln!(f, "      Bounds:     -")?,
+            }
+            match scan.intensity_limits {
+                Some([min, max]) => writeln!(f, "      Intensity:  {min} to {max}")?,
+                None => writeln!(f, "      Intensity:  -")?,
+            }
+        }
+
+        write!(f, "Images:            {}", self.images.len())?;
+        for image in &self.images {
+            writeln!(f)?;
+            write!(
+                f,
+                "  [{}] {}: {}",
+                image.index,
+                or_dash(image.name.as_deref()),
+                or_dash(image.projection.as_deref())
+            )?;
+            if let (Some(width), Some(height)) = (image.width, image.height) {
+                write!(f, " {width}x{height}")?;
+            }
+            if let Some(guid) = &image.pointcloud_guid {
+                write!(f, ", scan {guid}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{FILE_GUID, cartesian_prototype, write_e57_with};
+    use e57::{RecordDataType, RecordValue};
+
+    #[test]
+    fn test_attributes() {
+        let record = |name| Record {
+            name,
+            data_type: RecordDataType::F64,
+        };
+        let unknown = |namespace: &str, name: &str| {
+            record(RecordName::Unknown {
+                namespace: String::from(namespace),
+                name: String::from(name),
+            })
+        };
+        let prototype = vec![
+            record(RecordName::CartesianX),
+            record(RecordName::CartesianY),
+            record(RecordName::CartesianZ),
+            record(RecordName::Intensity),
+            unknown("nor", "normalX"),
+            unknown("nor", "normalY"),
+            unknown("nor", "normalZ"),
+            unknown("vendor", "quality"),
+        ];
+
+        assert_eq!(
+            attributes(&prototype),
+            ["cartesian", "intensity", "normals", "vendor:quality"]
+        );
+    }
+
+    #[test]
+    fn test_inspect_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("info.e57");
+
+        let mut prototype = cartesian_prototype();
+        prototype.push(Record {
+            name: RecordName::Intensity,
+            data_type: RecordDataType::Integer { min: 0, max: 2047 },
+        });
+        let points = [1.0, 2.0]
+            .map(|value| {
+                vec![
+                    RecordValue::Double(value),
+                    RecordValue::Double(value),
+                    RecordValue::Double(value),
+                    RecordValue::Integer(100),
+                ]
+            })
+            .to_vec();
+        write_e57_with(
+            &path,
+            prototype,
+            points,
+            |pointcloud_writer| pointcloud_writer.set_name(Some(String::from("Scan 01"))),
+            |writer| writer.set_coordinate_metadata(Some(String::from("EPSG:2056"))),
+        );
+
+        let info = inspect_file(&path).expect("Failed to inspect E57 file");
+        assert_eq!(info.guid, FILE_GUID);
+        assert_eq!(info.format_name, "ASTM E57 3D Imaging Data File");
+        assert_eq!(info.version, "1.0");
+        assert_eq!(info.coordinate_metadata.as_deref(), Some("EPSG:2056"));
+        assert!(info.images.is_empty());
+
+        let scan = &info.scans[0];
+        assert_eq!(scan.name.as_deref(), Some("Scan 01"));
+        assert_eq!(scan.point_count, 2);
+        assert_eq!(scan.attributes, ["cartesian", "intensity"]);
+        assert_eq!(scan.intensity_limits, Some([0.0, 2047.0]));
+        assert!(scan.pose.is_none());
+        let bounds = scan.bounds.as_ref().expect("Missing bounds");
+        assert_eq!((bounds.min.x, bounds.max.z), (1.0, 2.0));
+
+        let text = info.to_string();
+        assert!(text.contains("Scans:             1"));
+        assert!(text.contains("Points:     2"));
+
+        let json = serde_json::to_value(&info).expect("Failed to serialize info");
+        assert_eq!(json["scans"][0]["pointCount"], 2);
+        assert_eq!(json["formatName"], "ASTM E57 3D Imaging Data File");
+    }
+}
@@ -0,0 +1,319 @@
+use e57::{PointCloud, RecordDataType, RecordName, RecordValue};
+
+use crate::{Error, Result};
+
+/// How the E57 intensities are mapped to LAS intensities (u16).
+///
+/// # Example
+/// ```
+/// use e57_to_las::IntensityMode;
+///
+/// assert_eq!(IntensityMode::try_from("raw").ok(), Some(IntensityMode::Raw));
+/// assert_eq!(
+///     IntensityMode::try_from("0,2047,0.5").ok(),
+///     Some(IntensityMode::Custom { min: 0.0, max: 2047.0, gamma: 0.5 })
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IntensityMode {
+    /// Stretches the `intensityLimits` of the point clouds (or the range of
+    /// their intensity data type when missing, 0–1 for floating point
+    /// intensities) to the full u16 range. All the point clouds of a merged
+    /// file share the union of their limits, so that equal E57 intensities get
+    /// equal LAS intensities.
+    #[default]
+    Normalize,
+    /// Keeps the E57 intensity values, rounded and clamped to u16, e.g. for
+    /// scanners storing 0–2047 integers.
+    Raw,
+    /// Stretches `min..max` to the full u16 range, clamping values outside of
+    /// it, then applies a `gamma` correction (`t^(1/gamma)`, so that gammas
+    /// above 1 brighten dark values).
+    Custom { min: f64, max: f64, gamma: f64 },
+}
+
+impl IntensityMode {
+    /// Returns the mapping of this mode for the intensities of `pointclouds`.
+    pub(crate) fn mapping<'a>(
+        &self,
+        pointclouds: impl IntoIterator<Item = &'a PointCloud>,
+    ) -> IntensityMapping {
+        match *self {
+            IntensityMode::Raw => IntensityMapping::Raw,
+            IntensityMode::Custom { min, max, gamma } => {
+                IntensityMapping::Linear { min, max, gamma }
+            }
+            IntensityMode::Normalize => {
+                let (min, max) = pointclouds
+                    .into_iter()
+                    .filter(|pointcloud| {
+                        pointcloud
+                            .prototype
+                            .iter()
+                            .any(|record| record.name == RecordName::Intensity)
+                    })
+                    // Floating point intensities without limits are usually
+                    // already normalized.
+                    .map(|pointcloud| intensity_range(pointcloud).unwrap_or((0.0, 1.0)))
+                    .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+                    .unwrap_or((0.0, 1.0));
+                IntensityMapping::Linear {
+                    min,
+                    max,
+                    gamma: 1.0,
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<&str> for IntensityMode {
+    type Error = Error;
+
+    /// Parses `normalize`, `raw` or a custom `min,max[,gamma]` range.
+    fn try_from(value: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidIntensity(format!(
+                "{value:?}, expected `normalize`, `raw` or `min,max[,gamma]`"
+            ))
+        };
+
+        match value.trim() {
+            "normalize" => Ok(IntensityMode::Normalize),
+            "raw" => Ok(IntensityMode::Raw),
+            value => {
+                let numbers = value
+                    .split(',')
+                    .map(|number| number.trim().parse::<f64>().ok().filter(|n| n.is_finite()))
+                    .collect::<Option<Vec<f64>>>()
+                    .ok_or_else(invalid)?;
+                let (min, max, gamma) = match numbers[..] {
+                    [min, max] => (min, max, 1.0),
+                    [min, max, gamma] => (min, max, gamma),
+                    _ => return Err(invalid()),
+                };
+
+                if max <= min || gamma <= 0.0 {
+                    return Err(Error::InvalidIntensity(format!(
+                        "{value:?}, the maximum must be above the minimum and the gamma positive"
+                    )));
+                }
+
+                Ok(IntensityMode::Custom { min, max, gamma })
+            }
+        }
+    }
+}
+
+/// The mapping of E57 intensities to LAS intensities resolved from an
+/// [`IntensityMode`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum IntensityMapping {
+    Raw,
+    Linear { min: f64, max: f64, gamma: f64 },
+}
+
+impl IntensityMapping {
+    /// Returns the mapping of the intensities of `pointcloud` to the grey
+    /// color of its points without color: the mapping itself when it
+    /// normalizes them, or else the normalization by their limits.
+    pub(crate) fn grey(&self, pointcloud: &PointCloud) -> IntensityMapping {
+        match self {
+            IntensityMapping::Raw => IntensityMode::Normalize.mapping([pointcloud]),
+            mapping => *mapping,
+        }
+    }
+
+    /// Maps a raw (not normalized) E57 intensity to a LAS intensity.
+    pub(crate) fn apply(&self, intensity: f64) -> u16 {
+        let value = match *self {
+            IntensityMapping::Raw => intensity,
+            IntensityMapping::Linear { min, max, gamma } => {
+                let normalized = if max > min {
+                    ((intensity - min) / (max - min)).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                normalized.powf(1.0 / gamma) * f64::from(u16::MAX)
+            }
+        };
+
+        value.round().clamp(0.0, f64::from(u16::MAX)) as u16
+    }
+}
+
+/// Returns the intensity range of a point cloud: its `intensityLimits`, or the
+/// bounds of its intensity data type when missing. Returns `None` for point
+/// clouds without intensity or with unbounded floating point intensities.
+pub(crate) fn intensity_range(pointcloud: &PointCloud) -> Option<(f64, f64)> {
+    let data_type = &pointcloud
+        .prototype
+        .iter()
+        .find(|record| record.name == RecordName::Intensity)?
+        .data_type;
+
+    let limits = pointcloud.intensity_limits.as_ref().and_then(|limits| {
+        Some((
+            limit_value(limits.intensity_min.as_ref()?, data_type)?,
+            limit_value(limits.intensity_max.as_ref()?, data_type)?,
+        ))
+    });
+
+    let range = limits.or(match *data_type {
+        RecordDataType::Single {
+            min: Some(min),
+            max: Some(max),
+        } => Some((f64::from(min), f64::from(max))),
+        RecordDataType::Double {
+            min: Some(min),
+            max: Some(max),
+        } => Some((min, max)),
+        RecordDataType::ScaledInteger {
+            min,
+            max,
+            scale,
+            offset,
+        } => Some((min as f64 * scale + offset, max as f64 * scale + offset)),
+        RecordDataType::Integer { min, max } => Some((min as f64, max as f64)),
+        _ => None,
+    });
+
+    range.filter(|(min, max)| max > min)
+}
+
+/// Converts an intensity limit to f64. Scaled integer limits only make sense
+/// along with the scale and offset of a scaled integer intensity.
+fn limit_value(value: &RecordValue, data_type: &RecordDataType) -> Option<f64> {
+    match value {
+        RecordValue::ScaledInteger(_) => value.to_f64(data_type).ok(),
+        RecordValue::Single(value) => Some(f64::from(*value)),
+        RecordValue::Double(value) => Some(*value),
+        RecordValue::Integer(value) => Some(*value as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_intensity_mode() {
+        assert_eq!(
+            IntensityMode::try_from("normalize").ok(),
+            Some(IntensityMode::Normalize)
+        );
+        assert_eq!(
+            IntensityMode::try_from("0, 2047").ok(),
+            Some(IntensityMode::Custom {
+                min: 0.0,
+                max: 2047.0,
+                gamma: 1.0
+            })
+        );
+        assert!(IntensityMode::try_from("2047,0").is_err());
+        assert!(IntensityMode::try_from("0,1,0").is_err());
+        assert!(IntensityMode::try_from("0,1,2,3").is_err());
+        assert!(IntensityMode::try_from("bright").is_err());
+    }
+
+    #[test]
+    fn test_apply_mapping() {
+        let raw = IntensityMapping::Raw;
+        assert_eq!(raw.apply(2047.4), 2047);
+        assert_eq!(raw.apply(-3.0), 0);
+        assert_eq!(raw.apply(1e6), u16::MAX);
+
+        let linear = IntensityMapping::Linear {
+            min: 0.0,
+            max: 2047.0,
+            gamma: 1.0,
+        };
+        assert_eq!(linear.apply(0.0), 0);
+        assert_eq!(linear.apply(2047.0), u16::MAX);
+        assert_eq!(linear.apply(4000.0), u16::MAX);
+
+        let gamma = IntensityMapping::Linear {
+            min: 0.0,
+            max: 1.0,
+            gamma: 2.0,
+        };
+        assert_eq!(gamma.apply(0.25), u16::MAX / 2 + 1);
+    }
+
+    fn pointcloud(data_type: RecordDataType) -> PointCloud {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.prototype = vec![e57::Record {
+            name: RecordName::Intensity,
+            data_type,
+        }];
+        pointcloud
+    }
+
+    #[test]
+    fn test_normalize_uses_union_of_limits() {
+        let integers = pointcloud(RecordDataType::Integer { min: 0, max: 2047 });
+        let mut limited = pointcloud(RecordDataType::Integer { min: 0, max: 4095 });
+        limited.intensity_limits = Some(e57::IntensityLimits {
+            intensity_min: Some(RecordValue::Integer(100)),
+            intensity_max: Some(RecordValue::Integer(3000)),
+        });
+        let floats = pointcloud(RecordDataType::F32);
+
+        assert_eq!(
+            IntensityMode::Normalize.mapping([&integers, &limited, &floats]),
+            IntensityMapping::Linear {
+                min: 0.0,
+                max: 3000.0,
+                gamma: 1.0
+            }
+        );
+        assert_eq!(
+            IntensityMode::Normalize.mapping([&floats]),
+            IntensityMapping::Linear {
+                min: 0.0,
+                max: 1.0,
+                gamma: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_normalize_mixed_float_and_integer_intensities() {
+        // Floating point intensities without limits count as 0–1 in the
+        // union, instead of being left out of it and mapped to black.
+        let mut integers = pointcloud(RecordDataType::Integer { min: 0, max: 4095 });
+        integers.intensity_limits = Some(e57::IntensityLimits {
+            intensity_min: Some(RecordValue::Integer(100)),
+            intensity_max: Some(RecordValue::Integer(3000)),
+        });
+        let floats = pointcloud(RecordDataType::F32);
+        let mut without_intensity = PointCloud::default();
+        without_intensity.prototype = vec![e57::Record {
+            name: RecordName::CartesianX,
+            data_type: RecordDataType::F64,
+        }];
+
+        assert_eq!(
+            IntensityMode::Normalize.mapping([&integers, &floats, &without_intensity]),
+            IntensityMapping::Linear {
+                min: 0.0,
+                max: 3000.0,
+                gamma: 1.0
+            }
+        );
+
+        let mut half = pointcloud(RecordDataType::F64);
+        half.intensity_limits = Some(e57::IntensityLimits {
+            intensity_min: Some(RecordValue::Double(0.5)),
+            intensity_max: Some(RecordValue::Double(0.75)),
+        });
+        assert_eq!(
+            IntensityMode::Normalize.mapping([&half, &floats]),
+            IntensityMapping::Linear {
+                min: 0.0,
+                max: 1.0,
+                gamma: 1.0
+            }
+        );
+    }
+}
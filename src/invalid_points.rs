@@ -0,0 +1,108 @@
+use crate::{Error, Result};
+
+/// Highest classification of the legacy LAS point formats (0 to 5).
+const MAX_LEGACY_CLASSIFICATION: u8 = 31;
+
+/// What to do with the E57 points without valid coordinates (and, unless they
+/// are dropped, with an invalid color), e.g. to keep the full structured scan
+/// grid for scanner diagnostics.
+///
+/// Kept points without coordinates are placed at the scanner position. The
+/// points with only a direction follow the [`crate::DirectionPolicy`] instead,
+/// so they are dropped with [`crate::DirectionPolicy::Drop`] whatever this
+/// policy.
+///
+/// # Example
+/// ```
+/// use e57_to_las::InvalidPoints;
+///
+/// assert_eq!(
+///     InvalidPoints::try_from("classify:7").ok(),
+///     Some(InvalidPoints::Classify(las::point::Classification::LowPoint))
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum InvalidPoints {
+    /// Skips the points without valid coordinates; points with an invalid
+    /// color are written as is.
+    #[default]
+    Drop,
+    /// Keeps them, flagged as withheld so that they are ignored by most LAS
+    /// readers.
+    Withheld,
+    /// Keeps them with a dedicated classification, e.g. 7 (low point, noise).
+    Classify(las::point::Classification),
+}
+
+impl InvalidPoints {
+    /// Returns true if the invalid points are written.
+    pub(crate) fn keeps(&self) -> bool {
+        *self != InvalidPoints::Drop
+    }
+
+    /// Flags an invalid point.
+    pub(crate) fn flag(&self, point: &mut las::Point) {
+        match *self {
+            InvalidPoints::Drop => {}
+            InvalidPoints::Withheld => point.is_withheld = true,
+            InvalidPoints::Classify(classification) => point.classification = classification,
+        }
+    }
+}
+
+impl TryFrom<&str> for InvalidPoints {
+    type Error = Error;
+
+    /// Parses `drop`, `withheld` or `classify:<class>`, with a class between 0
+    /// and 31 (the range of the legacy point formats) other than 12.
+    fn try_from(value: &str) -> Result<Self> {
+        match value.trim() {
+            "drop" => Ok(InvalidPoints::Drop),
+            "withheld" => Ok(InvalidPoints::Withheld),
+            value => value
+                .strip_prefix("classify:")
+                .and_then(|class| class.trim().parse::<u8>().ok())
+                .filter(|class| *class <= MAX_LEGACY_CLASSIFICATION)
+                .and_then(|class| las::point::Classification::new(class).ok())
+                .map(InvalidPoints::Classify)
+                .ok_or_else(|| {
+                    Error::InvalidPointsPolicy(format!(
+                        "{value:?}, expected `drop`, `withheld` or `classify:<class>` with a class between 0 and 31, other than 12 (overlap)"
+                    ))
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use las::point::Classification;
+
+    #[test]
+    fn test_parse_invalid_points() {
+        assert_eq!(
+            InvalidPoints::try_from("withheld").ok(),
+            Some(InvalidPoints::Withheld)
+        );
+        assert_eq!(
+            InvalidPoints::try_from("classify:18").ok(),
+            Some(InvalidPoints::Classify(Classification::HighNoise))
+        );
+        assert!(InvalidPoints::try_from("classify:12").is_err());
+        assert!(InvalidPoints::try_from("classify:64").is_err());
+        assert!(InvalidPoints::try_from("keep").is_err());
+    }
+
+    #[test]
+    fn test_flag_point() {
+        let mut point = las::Point::default();
+        InvalidPoints::Withheld.flag(&mut point);
+        assert!(point.is_withheld);
+
+        let mut point = las::Point::default();
+        InvalidPoints::Classify(Classification::LowPoint).flag(&mut point);
+        assert!(!point.is_withheld);
+        assert_eq!(point.classification, Classification::LowPoint);
+    }
+}
@@ -4,11 +4,21 @@ const ALLOWED_VERSIONS: [(u8, u8); 5] = [(1, 0), (1, 1), (1, 2), (1, 3), (1, 4)]
 const MIN_VERSION: (u8, u8) = ALLOWED_VERSIONS[0];
 const MAX_VERSION: (u8, u8) = ALLOWED_VERSIONS[ALLOWED_VERSIONS.len() - 1];
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LasVersion {
     major: u8,
     minor: u8,
 }
 
+impl Default for LasVersion {
+    fn default() -> Self {
+        Self {
+            major: MAX_VERSION.0,
+            minor: MAX_VERSION.1,
+        }
+    }
+}
+
 impl LasVersion {
     pub fn new(major: u8, minor: u8) -> Result<Self> {
         if !ALLOWED_VERSIONS.contains(&(major, minor)) {
@@ -9,18 +9,58 @@
 )]
 #![warn(clippy::unwrap_used)]
 
+mod batch;
+#[cfg(feature = "colorize")]
+mod colorize;
 mod convert_file;
+mod convert_options;
 mod convert_point;
 mod convert_pointcloud;
+mod coordinates;
+#[cfg(feature = "copc")]
+mod copc_writer;
+mod crs;
 mod error;
+mod extra_bytes;
 mod get_las_writer;
+mod header_metadata;
+mod images;
+mod info;
+mod intensity;
+mod invalid_points;
 mod las_version;
+mod name_template;
+mod octree;
+mod point_format;
+mod point_source_ids;
+mod pose;
+mod potree_writer;
+mod raw_fields;
+mod scale_offset;
 mod spatial_point;
 mod stations;
+#[cfg(test)]
+pub(crate) mod test_utils;
 mod utils;
+mod validate;
 
-pub use self::convert_file::convert_file;
+pub use self::batch::{BatchReport, convert_files_with_options, expand_inputs};
+pub use self::convert_file::{convert_file, convert_file_with_options, merge_files_with_options};
+pub use self::convert_options::{ConvertOptions, DEFAULT_BUFFER_SIZE};
 pub use self::convert_point::convert_point;
-pub use self::convert_pointcloud::convert_pointcloud;
+pub use self::convert_pointcloud::{convert_pointcloud, convert_pointcloud_with_options};
+pub use coordinates::DirectionPolicy;
+pub use crs::Crs;
 pub use error::{Error, Result};
+pub use info::{FileInfo, ImageInfo, ScanBounds, ScanInfo, ScanPose, inspect_file};
+pub use intensity::IntensityMode;
+pub use invalid_points::InvalidPoints;
 pub use las_version::LasVersion;
+pub use name_template::NameTemplate;
+pub use point_format::PointFormat;
+pub use point_source_ids::PointSourceIds;
+pub use pose::{Pose, RigidTransform};
+pub use scale_offset::{Offset, Scale};
+pub use spatial_point::SpatialPoint;
+pub use stations::StationsFormat;
+pub use validate::{ValidationIssue, validate_file};
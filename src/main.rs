@@ -1,39 +1,254 @@
 use anyhow::Context;
-use clap::Parser;
-use e57_to_las::{LasVersion, Result, convert_file};
+use clap::{Parser, Subcommand};
+use e57_to_las::{
+    ConvertOptions, Crs, DEFAULT_BUFFER_SIZE, DirectionPolicy, IntensityMode, InvalidPoints,
+    LasVersion, NameTemplate, Offset, PointFormat, PointSourceIds, Pose, Result, RigidTransform,
+    Scale, StationsFormat, convert_file_with_options, convert_files_with_options, expand_inputs,
+    inspect_file, merge_files_with_options, validate_file,
+};
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
-    #[arg(short, long)]
-    path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, required = true, num_args = 1..)]
+    path: Vec<String>,
 
     #[arg(short, long, default_value_t = String::from("./"))]
     output: String,
 
+    #[arg(long, default_value_t = false)]
+    merge: bool,
+
     #[arg(short = 'T', long, default_value_t = 0)]
     threads: usize,
 
     #[arg(short = 'S', long, default_value_t = false)]
     stations: bool,
 
+    #[arg(long, default_value_t = String::from("legacy"))]
+    stations_format: String,
+
+    #[arg(long, default_value_t = String::from("{index}"))]
+    name_template: String,
+
     #[arg(short = 'L', long, default_value_t = String::from("1.4"))]
     las_version: String,
+
+    #[arg(short = 'F', long, default_value_t = String::from("auto"))]
+    point_format: String,
+
+    #[arg(long, default_value_t = false)]
+    streaming: bool,
+
+    #[arg(long, default_value_t = DEFAULT_BUFFER_SIZE)]
+    buffer_size: usize,
+
+    #[arg(short = 'C', long, default_value_t = false)]
+    compress: bool,
+
+    #[arg(long, default_value_t = false)]
+    copc: bool,
+
+    #[arg(long, default_value_t = false)]
+    potree: bool,
+
+    #[arg(long)]
+    crs: Option<String>,
+
+    #[arg(long, default_value_t = String::from("auto"))]
+    scale: String,
+
+    #[arg(long, default_value_t = String::from("auto"))]
+    offset: String,
+
+    #[arg(long, default_value_t = false)]
+    adjusted_gps_time: bool,
+
+    #[arg(long, default_value_t = String::from("normalize"))]
+    intensity: String,
+
+    #[arg(long, default_value_t = String::from("apply"))]
+    pose: String,
+
+    #[arg(long)]
+    transform: Option<String>,
+
+    #[arg(long, default_value_t = String::from("drop"))]
+    direction_points: String,
+
+    #[arg(long, default_value_t = String::from("drop"))]
+    invalid_points: String,
+
+    #[arg(long, default_value_t = false)]
+    images: bool,
+
+    #[arg(long, default_value_t = false)]
+    colorize: bool,
+
+    #[arg(long, default_value_t = String::from("index"))]
+    point_source_ids: String,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the metadata, scans and images of an E57 file without converting it.
+    Info {
+        /// The path to the E57 file.
+        path: String,
+
+        /// Print the information as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Check the integrity of an E57 file (page checksums, XML section,
+    /// prototypes, record counts and image blobs) and exit with an error if it
+    /// has any problem.
+    Validate {
+        /// The path to the E57 file.
+        path: String,
+
+        /// Print the problems as JSON.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    match args.command {
+        Some(Command::Info { path, json }) => {
+            let info = inspect_file(&path)?;
+            if json {
+                let json =
+                    serde_json::to_string_pretty(&info).context("Failed to serialize info")?;
+                println!("{json}");
+            } else {
+                println!("{info}");
+            }
+            return Ok(());
+        }
+        Some(Command::Validate { path, json }) => {
+            let issues = validate_file(&path)?;
+            if json {
+                let json =
+                    serde_json::to_string_pretty(&issues).context("Failed to serialize issues")?;
+                println!("{json}");
+            } else if issues.is_empty() {
+                println!("{path} is valid");
+            } else {
+                for issue in &issues {
+                    println!("{issue}");
+                }
+            }
+
+            if !issues.is_empty() {
+                eprintln!("{path} has {} problem(s)", issues.len());
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let stations_formats = StationsFormat::parse_list(&args.stations_format)?;
+    let name_template = NameTemplate::try_from(args.name_template.as_str())?;
     let las_version = LasVersion::try_from(args.las_version.as_str())?;
+    let point_format = PointFormat::try_from(args.point_format.as_str())?;
+
+    let crs = args.crs.as_deref().map(Crs::try_from).transpose()?;
+
+    let scale = Scale::parse_axes(&args.scale)?;
+    let offset = Offset::parse_axes(&args.offset)?;
 
-    convert_file(
-        args.path,
-        args.output,
-        args.threads,
-        args.stations,
+    let intensity = IntensityMode::try_from(args.intensity.as_str())?;
+
+    let pose = Pose::try_from(args.pose.as_str())?;
+
+    let transform = args
+        .transform
+        .map(|transform| -> anyhow::Result<RigidTransform> {
+            let matrix = inline_or_file(&transform, "transform")?;
+            Ok(RigidTransform::try_from(matrix.as_str())?)
+        })
+        .transpose()?;
+
+    let point_source_ids = inline_or_file(&args.point_source_ids, "point source IDs")?;
+    let point_source_ids = PointSourceIds::try_from(point_source_ids.as_str())?;
+
+    let direction_points = DirectionPolicy::try_from(args.direction_points.as_str())?;
+    let invalid_points = InvalidPoints::try_from(args.invalid_points.as_str())?;
+
+    let options = ConvertOptions {
+        number_of_threads: args.threads,
+        as_stations: args.stations,
+        stations_formats,
         las_version,
-    )
-    .context("Failed to convert file")?;
+        point_format,
+        streaming: args.streaming,
+        buffer_size: args.buffer_size,
+        compress: args.compress,
+        copc: args.copc,
+        potree: args.potree,
+        crs,
+        scale,
+        offset,
+        adjusted_gps_time: args.adjusted_gps_time,
+        intensity,
+        pose,
+        transform,
+        direction_points,
+        invalid_points,
+        images: args.images,
+        colorize: args.colorize,
+        point_source_ids,
+        name_template,
+    };
+
+    if args.merge {
+        let inputs = expand_inputs(&args.path)?;
+        merge_files_with_options(&inputs, &args.output, &options)
+            .context("Failed to merge files")?;
+        return Ok(());
+    }
+
+    // A single file is converted in the output directory itself, several
+    // inputs in a directory per file.
+    if let [path] = args.path.as_slice()
+        && std::path::Path::new(path).is_file()
+    {
+        convert_file_with_options(path.clone(), args.output, &options)
+            .context("Failed to convert file")?;
+        return Ok(());
+    }
+
+    let inputs = expand_inputs(&args.path)?;
+    let report = convert_files_with_options(&inputs, &args.output, &options)?;
+    println!("{report}");
+    if !report.is_success() {
+        eprintln!("{} file(s) failed to convert", report.failed.len());
+        std::process::exit(1);
+    }
 
     Ok(())
 }
+
+/// Returns the value of an argument given inline, or read from the file whose
+/// path follows an `@` (e.g. `@transform.txt`).
+fn inline_or_file(value: &str, name: &str) -> anyhow::Result<String> {
+    match value.strip_prefix('@') {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {name} file {path}")),
+        None => Ok(value.to_owned()),
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+
+use e57::PointCloud;
+
+use crate::{Error, Result};
+
+/// Extensions stripped from the end of a template, since the extension of the
+/// files depends on the output format.
+const EXTENSIONS: [&str; 3] = [".copc.laz", ".laz", ".las"];
+
+/// A part of a [`NameTemplate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// The index of the point cloud, zero-padded to `width` digits.
+    Index {
+        width: usize,
+    },
+    Name,
+    Guid,
+    FileStem,
+}
+
+/// The template of the names of the per-station LAS files, e.g.
+/// `{file_stem}_{index:03}_{name}` or `{guid}`.
+///
+/// The placeholders are `{index}` (optionally zero-padded, e.g. `{index:03}`),
+/// `{name}` and `{guid}` of the E57 point cloud, and `{file_stem}` of the E57
+/// file. The extension (`.las`, `.laz` or `.copc.laz`) follows the output
+/// format, so a trailing one in the template is ignored. The characters not
+/// allowed in file names are replaced by `_`, and repeated names get a `_2`,
+/// `_3`... suffix.
+///
+/// # Example
+/// ```
+/// use e57_to_las::NameTemplate;
+///
+/// assert!(NameTemplate::try_from("{file_stem}_{index:03}_{name}.las").is_ok());
+/// assert!(NameTemplate::try_from("{station}").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+impl Default for NameTemplate {
+    /// `{index}`, i.e. `0.las`, `1.las`...
+    fn default() -> Self {
+        Self {
+            parts: vec![Part::Index { width: 0 }],
+        }
+    }
+}
+
+impl NameTemplate {
+    /// Returns the file name of a point cloud, without collision handling.
+    pub(crate) fn file_name(
+        &self,
+        index: usize,
+        pointcloud: &PointCloud,
+        file_stem: &str,
+        extension: &str,
+    ) -> String {
+        let stem = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.clone(),
+                Part::Index { width } => format!("{index:0width$}"),
+                Part::Name => pointcloud.name.clone().unwrap_or_default(),
+                Part::Guid => pointcloud.guid.clone().unwrap_or_default(),
+                Part::FileStem => String::from(file_stem),
+            })
+            .collect::<String>();
+
+        let stem = sanitize(&stem);
+        let stem = if stem.is_empty() {
+            index.to_string()
+        } else {
+            stem
+        };
+        format!("{stem}.{extension}")
+    }
+
+    /// Returns the file names of point clouds (given with the stem of their
+    /// E57 file), indexed by their position, adding a `_2`, `_3`... suffix to
+    /// the names already taken, regardless of case.
+    pub(crate) fn file_names(
+        &self,
+        pointclouds: &[(&str, &PointCloud)],
+        extension: &str,
+    ) -> Vec<String> {
+        let mut taken = HashSet::new();
+        pointclouds
+            .iter()
+            .enumerate()
+            .map(|(index, (file_stem, pointcloud))| {
+                let name = self.file_name(index, pointcloud, file_stem, extension);
+                let stem = name
+                    .strip_suffix(&format!(".{extension}"))
+                    .unwrap_or(&name)
+                    .to_owned();
+
+                let mut name = name;
+                let mut count = 1;
+                while !taken.insert(name.to_lowercase()) {
+                    count += 1;
+                    name = format!("{stem}_{count}.{extension}");
+                }
+                name
+            })
+            .collect()
+    }
+}
+
+/// Replaces the characters not allowed in file names on common platforms
+/// (path separators, reserved and control characters) by `_`, and trims the
+/// leading and trailing spaces and dots.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim_matches([' ', '.'])
+        .to_owned()
+}
+
+impl TryFrom<&str> for NameTemplate {
+    type Error = Error;
+
+    /// Parses a template made of text and `{index}`, `{index:0<width>}`,
+    /// `{name}`, `{guid}` and `{file_stem}` placeholders.
+    fn try_from(value: &str) -> Result<Self> {
+        let mut template = value.trim();
+        for extension in EXTENSIONS {
+            let length = template.len().saturating_sub(extension.len());
+            if template.len() > extension.len()
+                && template.is_char_boundary(length)
+                && template[length..].eq_ignore_ascii_case(extension)
+            {
+                template = &template[..length];
+                break;
+            }
+        }
+
+        let invalid = |reason: &str| Error::InvalidNameTemplate(format!("{value:?}, {reason}"));
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                parts.push(Part::Literal(String::from(rest)));
+                break;
+            };
+            if start > 0 {
+                parts.push(Part::Literal(String::from(&rest[..start])));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| invalid("expected `}` after `{`"))?
+                + start;
+            let placeholder = &rest[start + 1..end];
+            parts.push(match placeholder {
+                "index" => Part::Index { width: 0 },
+                "name" => Part::Name,
+                "guid" => Part::Guid,
+                "file_stem" => Part::FileStem,
+                _ => placeholder
+                    .strip_prefix("index:")
+                    .filter(|width| width.starts_with('0'))
+                    .and_then(|width| width.parse::<usize>().ok())
+                    .filter(|width| *width <= 20)
+                    .map(|width| Part::Index { width })
+                    .ok_or_else(|| {
+                        invalid(&format!(
+                            "unknown placeholder {{{placeholder}}}, expected {{index}}, {{index:0<width>}}, {{name}}, {{guid}} or {{file_stem}}"
+                        ))
+                    })?,
+            });
+            rest = &rest[end + 1..];
+        }
+
+        if parts.is_empty() {
+            return Err(invalid("expected at least one placeholder or character"));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn pointcloud(name: Option<&str>, guid: &str) -> PointCloud {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.name = name.map(String::from);
+        pointcloud.guid = Some(String::from(guid));
+        pointcloud
+    }
+
+    #[test]
+    fn test_parse_name_template() {
+        assert_eq!(
+            NameTemplate::try_from("{index}").ok(),
+            Some(NameTemplate::default())
+        );
+        assert_eq!(
+            NameTemplate::try_from("scan_{index:03}.LAS").ok(),
+            Some(NameTemplate {
+                parts: vec![
+                    Part::Literal(String::from("scan_")),
+                    Part::Index { width: 3 }
+                ],
+            })
+        );
+        assert!(NameTemplate::try_from("{index:3}").is_err());
+        assert!(NameTemplate::try_from("{name").is_err());
+        assert!(NameTemplate::try_from("{station}").is_err());
+        assert!(NameTemplate::try_from(" ").is_err());
+    }
+
+    #[test]
+    fn test_file_name() {
+        let template =
+            NameTemplate::try_from("{file_stem}_{index:03}_{name}.las").expect("Invalid template");
+        let scan = pointcloud(Some("Scan 01/north: \"A\""), "a0000000-0001");
+
+        assert_eq!(
+            template.file_name(7, &scan, "site", "laz"),
+            "site_007_Scan 01_north_ _A_.laz"
+        );
+
+        // An empty name falls back to the index.
+        let template = NameTemplate::try_from("{name}").expect("Invalid template");
+        assert_eq!(
+            template.file_name(3, &pointcloud(None, "guid"), "site", "las"),
+            "3.las"
+        );
+    }
+
+    #[test]
+    fn test_file_names_collisions() {
+        let template = NameTemplate::try_from("{name}").expect("Invalid template");
+        let first = pointcloud(Some("Scan"), "1");
+        let second = pointcloud(Some("scan"), "2");
+        let third = pointcloud(Some("Scan"), "3");
+        let other = pointcloud(Some("Other"), "4");
+
+        assert_eq!(
+            template.file_names(
+                &[("a", &first), ("a", &second), ("b", &third), ("b", &other)],
+                "las"
+            ),
+            ["Scan.las", "scan_2.las", "Scan_3.las", "Other.las"]
+        );
+    }
+}
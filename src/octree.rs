@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::get_las_writer::PointBounds;
+
+/// Number of cells per axis of the occupancy grid of every octree node: a node
+/// keeps at most one point per cell, the others are pushed down to its children.
+const GRID_SIZE: u64 = 128;
+/// Hard limit on the octree depth, reached only for very dense clouds.
+const MAX_DEPTH: i32 = 24;
+
+/// Key of an octree node: its level and its position in the grid of that level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct VoxelKey {
+    pub(crate) level: i32,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) z: i32,
+}
+
+/// The points of an octree node and the grid cells they occupy.
+#[derive(Default)]
+pub(crate) struct OctreeNode {
+    pub(crate) points: Vec<las::Point>,
+    occupied: HashSet<u32>,
+}
+
+/// The cube enclosing all points, used as the root node of the octree.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Cube {
+    pub(crate) center: las::Vector<f64>,
+    pub(crate) halfsize: f64,
+}
+
+impl VoxelKey {
+    /// The key of the root node.
+    pub(crate) const ROOT: VoxelKey = VoxelKey {
+        level: 0,
+        x: 0,
+        y: 0,
+        z: 0,
+    };
+
+    /// Returns the key of one of the eight children of this node, with `index`
+    /// holding the x, y and z halves as bits 2, 1 and 0.
+    pub(crate) fn child(&self, index: u8) -> VoxelKey {
+        VoxelKey {
+            level: self.level + 1,
+            x: self.x * 2 + i32::from((index >> 2) & 1),
+            y: self.y * 2 + i32::from((index >> 1) & 1),
+            z: self.z * 2 + i32::from(index & 1),
+        }
+    }
+}
+
+impl Cube {
+    pub(crate) fn from_bounds(bounds: &PointBounds) -> Self {
+        let center = las::Vector {
+            x: (bounds.min.x + bounds.max.x) / 2.0,
+            y: (bounds.min.y + bounds.max.y) / 2.0,
+            z: (bounds.min.z + bounds.max.z) / 2.0,
+        };
+        let halfsize = (bounds.max.x - bounds.min.x)
+            .max(bounds.max.y - bounds.min.y)
+            .max(bounds.max.z - bounds.min.z)
+            / 2.0;
+
+        // Points lying exactly on the max faces must still fall inside the cube.
+        Self {
+            center,
+            halfsize: halfsize.max(f64::EPSILON) * (1.0 + 1e-9),
+        }
+    }
+
+    /// Returns the spacing between points of the root node.
+    pub(crate) fn spacing(&self) -> f64 {
+        2.0 * self.halfsize / GRID_SIZE as f64
+    }
+
+    /// Returns the depth at which the grid spacing becomes smaller than `resolution`.
+    pub(crate) fn max_depth(&self, resolution: f64) -> i32 {
+        let depth = (self.spacing() / resolution).log2().ceil();
+        if depth.is_finite() {
+            (depth as i32).clamp(0, MAX_DEPTH)
+        } else {
+            0
+        }
+    }
+
+    /// Returns the corner of the cube with the smallest coordinates.
+    pub(crate) fn min(&self) -> las::Vector<f64> {
+        las::Vector {
+            x: self.center.x - self.halfsize,
+            y: self.center.y - self.halfsize,
+            z: self.center.z - self.halfsize,
+        }
+    }
+
+    /// Returns the corner of the cube with the largest coordinates.
+    pub(crate) fn max(&self) -> las::Vector<f64> {
+        las::Vector {
+            x: self.center.x + self.halfsize,
+            y: self.center.y + self.halfsize,
+            z: self.center.z + self.halfsize,
+        }
+    }
+
+    /// Returns the index of the cell containing `value` on one axis, in a grid
+    /// with `cells` cells per axis.
+    fn cell(&self, value: f64, center: f64, cells: u64) -> u64 {
+        let relative = (value - (center - self.halfsize)) / (2.0 * self.halfsize);
+        ((relative * cells as f64).floor().max(0.0) as u64).min(cells - 1)
+    }
+
+    /// Returns the key of the node containing `point` at `level`, and the index
+    /// of the occupancy grid cell containing it inside that node.
+    fn locate(&self, point: &las::Point, level: i32) -> (VoxelKey, u32) {
+        let cells = GRID_SIZE << level;
+        let x = self.cell(point.x, self.center.x, cells);
+        let y = self.cell(point.y, self.center.y, cells);
+        let z = self.cell(point.z, self.center.z, cells);
+
+        let key = VoxelKey {
+            level,
+            x: (x / GRID_SIZE) as i32,
+            y: (y / GRID_SIZE) as i32,
+            z: (z / GRID_SIZE) as i32,
+        };
+        let cell = ((x % GRID_SIZE) * GRID_SIZE * GRID_SIZE
+            + (y % GRID_SIZE) * GRID_SIZE
+            + (z % GRID_SIZE)) as u32;
+
+        (key, cell)
+    }
+}
+
+/// Distributes the points in an octree: every node keeps at most one point per
+/// cell of its occupancy grid, so that coarser levels hold an evenly spaced
+/// subset of the cloud, and the remaining points are pushed down to the children.
+pub(crate) fn build_octree(
+    points: impl IntoIterator<Item = las::Point>,
+    cube: &Cube,
+    max_depth: i32,
+) -> BTreeMap<VoxelKey, OctreeNode> {
+    let mut nodes: BTreeMap<VoxelKey, OctreeNode> = BTreeMap::new();
+
+    for point in points {
+        for level in 0..=max_depth {
+            let (key, cell) = cube.locate(&point, level);
+            let node = nodes.entry(key).or_default();
+
+            if level == max_depth || node.occupied.insert(cell) {
+                node.points.push(point);
+                break;
+            }
+        }
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> las::Point {
+        las::Point {
+            x,
+            y,
+            z,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_octree_keeps_one_point_per_cell() {
+        let mut bounds = PointBounds::default();
+        let points = vec![
+            point(0.0, 0.0, 0.0),
+            point(0.0, 0.0, 0.0),
+            point(10.0, 10.0, 10.0),
+        ];
+        for p in &points {
+            bounds.update(p);
+        }
+        let cube = Cube::from_bounds(&bounds);
+
+        let nodes = build_octree(points, &cube, 3);
+
+        assert_eq!(nodes[&VoxelKey::ROOT].points.len(), 2);
+        assert_eq!(nodes.values().map(|n| n.points.len()).sum::<usize>(), 3);
+
+        // The duplicated point is pushed down to the child containing it
+        assert_eq!(nodes[&VoxelKey::ROOT.child(0)].points.len(), 1);
+    }
+
+    #[test]
+    fn test_voxel_key_child() {
+        let key = VoxelKey {
+            level: 1,
+            x: 1,
+            y: 0,
+            z: 1,
+        };
+        assert_eq!(
+            key.child(0b101),
+            VoxelKey {
+                level: 2,
+                x: 3,
+                y: 0,
+                z: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_depth_follows_resolution() {
+        let cube = Cube {
+            center: las::Vector::default(),
+            halfsize: 64.0,
+        };
+        // Root spacing is 1.0: 10 levels to get down to 1 mm
+        assert_eq!(cube.max_depth(0.001), 10);
+        assert_eq!(cube.max_depth(1.0), 0);
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::anyhow;
+
+use crate::get_las_writer::PointAttributes;
+use crate::{Error, Result};
+
+/// Highest LAS point data record format number.
+const MAX_FORMAT: u8 = 10;
+/// Point formats with waveform packets, which E57 files have no data for.
+const WAVEFORM_FORMATS: [u8; 4] = [4, 5, 9, 10];
+/// Point formats allowed in COPC files.
+const COPC_FORMATS: [u8; 3] = [6, 7, 8];
+
+/// The LAS point data record format of the output files.
+///
+/// # Example
+/// ```
+/// use e57_to_las::PointFormat;
+///
+/// assert_eq!(PointFormat::try_from("7").ok(), Some(PointFormat::Fixed(7)));
+/// assert!(PointFormat::try_from("11").is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PointFormat {
+    /// The smallest format holding the attributes of the points (GPS time and
+    /// color): 0 to 3 before LAS 1.4, 6 or 7 for LAS 1.4.
+    #[default]
+    Auto,
+    /// A fixed format. The attributes of the points the format has no room for
+    /// are dropped (with a warning), and the attributes of the format the points
+    /// do not have (e.g. NIR, which E57 files do not hold) are written as zeros.
+    Fixed(u8),
+}
+
+impl TryFrom<&str> for PointFormat {
+    type Error = Error;
+
+    /// Parses `auto` or a point format number from 0 to 10.
+    fn try_from(value: &str) -> Result<Self> {
+        match value.trim() {
+            "auto" => Ok(PointFormat::Auto),
+            value => value
+                .parse::<u8>()
+                .ok()
+                .filter(|format| *format <= MAX_FORMAT)
+                .map(PointFormat::Fixed)
+                .ok_or_else(|| {
+                    Error::InvalidPointFormat(format!(
+                        "{value:?}, expected `auto` or a number from 0 to {MAX_FORMAT}"
+                    ))
+                }),
+        }
+    }
+}
+
+impl PointFormat {
+    /// Checks that the format can be written in a LAS file of `version` (or a
+    /// COPC file, which is always LAS 1.4).
+    pub(crate) fn check(&self, version: las::Version, copc: bool) -> Result<()> {
+        let PointFormat::Fixed(format) = *self else {
+            return Ok(());
+        };
+
+        if WAVEFORM_FORMATS.contains(&format) {
+            return Err(Error::InvalidPointFormat(format!(
+                "{format}, formats with waveform packets ({WAVEFORM_FORMATS:?}) are not supported since E57 files have no waveform data"
+            )));
+        }
+        if copc && !COPC_FORMATS.contains(&format) {
+            return Err(Error::InvalidPointFormat(format!(
+                "{format}, COPC files use point formats {COPC_FORMATS:?}"
+            )));
+        }
+        if !copc && format > max_format(version) {
+            return Err(Error::InvalidPointFormat(format!(
+                "{format}, LAS {version} supports point formats 0 to {}",
+                max_format(version)
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the LAS point format for points with `attributes` in a file of
+    /// `version`.
+    pub(crate) fn resolve(
+        &self,
+        version: las::Version,
+        copc: bool,
+        attributes: &PointAttributes,
+    ) -> anyhow::Result<las::point::Format> {
+        self.check(version, copc)?;
+
+        let number = match *self {
+            PointFormat::Auto if copc || version >= las::Version::new(1, 4) => {
+                if attributes.has_color { 7 } else { 6 }
+            }
+            PointFormat::Auto => {
+                u8::from(attributes.has_gps_time) + 2 * u8::from(attributes.has_color)
+            }
+            PointFormat::Fixed(number) => number,
+        };
+        let format = las::point::Format::new(number)
+            .map_err(|error| anyhow!("Invalid point format {number}: {error}"))?;
+
+        if attributes.has_color && !format.has_color {
+            eprintln!("Warning: point format {number} has no color, the E57 colors are dropped");
+        }
+        if attributes.has_gps_time && !format.has_gps_time {
+            eprintln!(
+                "Warning: point format {number} has no GPS time, the E57 timestamps are dropped"
+            );
+        }
+
+        Ok(format)
+    }
+}
+
+/// Returns the highest point format of a LAS version.
+fn max_format(version: las::Version) -> u8 {
+    match version.minor {
+        0 | 1 => 1,
+        2 => 3,
+        3 => 5,
+        _ => MAX_FORMAT,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn attributes(has_gps_time: bool, has_color: bool) -> PointAttributes {
+        PointAttributes {
+            has_gps_time,
+            has_color,
+            ..Default::default()
+        }
+    }
+
+    fn resolve(format: PointFormat, version: (u8, u8), attributes: PointAttributes) -> u8 {
+        format
+            .resolve(las::Version::from(version), false, &attributes)
+            .expect("Failed to resolve point format")
+            .to_u8()
+            .expect("Invalid point format")
+    }
+
+    #[test]
+    fn test_parse_point_format() {
+        assert_eq!(PointFormat::try_from("auto").ok(), Some(PointFormat::Auto));
+        assert_eq!(PointFormat::try_from("0").ok(), Some(PointFormat::Fixed(0)));
+        assert!(PointFormat::try_from("-1").is_err());
+    }
+
+    #[test]
+    fn test_auto_point_format() {
+        assert_eq!(
+            resolve(PointFormat::Auto, (1, 2), attributes(false, false)),
+            0
+        );
+        assert_eq!(
+            resolve(PointFormat::Auto, (1, 2), attributes(true, false)),
+            1
+        );
+        assert_eq!(
+            resolve(PointFormat::Auto, (1, 2), attributes(false, true)),
+            2
+        );
+        assert_eq!(
+            resolve(PointFormat::Auto, (1, 3), attributes(true, true)),
+            3
+        );
+        assert_eq!(
+            resolve(PointFormat::Auto, (1, 4), attributes(false, false)),
+            6
+        );
+        assert_eq!(
+            resolve(PointFormat::Auto, (1, 4), attributes(true, true)),
+            7
+        );
+    }
+
+    #[test]
+    fn test_fixed_point_format() {
+        assert_eq!(
+            resolve(PointFormat::Fixed(8), (1, 4), attributes(true, true)),
+            8
+        );
+        assert_eq!(
+            resolve(PointFormat::Fixed(1), (1, 4), attributes(true, true)),
+            1
+        );
+
+        let check = |format, version, copc| {
+            PointFormat::Fixed(format)
+                .check(las::Version::from(version), copc)
+                .is_ok()
+        };
+        assert!(check(3, (1, 2), false));
+        assert!(!check(6, (1, 3), false));
+        assert!(!check(2, (1, 1), false));
+        assert!(!check(4, (1, 4), false));
+        assert!(check(8, (1, 2), true));
+        assert!(!check(3, (1, 4), true));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use e57::PointCloud;
+
+use crate::{Error, Result};
+
+/// How the LAS point source ID of the points of each E57 point cloud is
+/// chosen in merged outputs, so that the points of each scan can be told
+/// apart.
+///
+/// # Example
+/// ```
+/// use e57_to_las::PointSourceIds;
+///
+/// assert_eq!(PointSourceIds::try_from("index").ok(), Some(PointSourceIds::Index));
+/// assert!(matches!(
+///     PointSourceIds::try_from("Station 1=101, Station 2=102"),
+///     Ok(PointSourceIds::Map(_))
+/// ));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PointSourceIds {
+    /// The index of the point cloud, counted across all the merged files.
+    #[default]
+    Index,
+    /// The ID given to the GUID, or else to the name, of every point cloud.
+    Map(BTreeMap<String, u16>),
+}
+
+impl PointSourceIds {
+    /// Returns the point source ID of each point cloud, failing for the ones
+    /// missing from a mapping.
+    pub(crate) fn resolve(&self, pointclouds: &[&PointCloud]) -> anyhow::Result<Vec<u16>> {
+        pointclouds
+            .iter()
+            .enumerate()
+            .map(|(index, pointcloud)| match self {
+                PointSourceIds::Index => u16::try_from(index).map_err(|_| {
+                    anyhow::anyhow!(
+                        "Point cloud {index} does not fit in a point source ID (at most {})",
+                        u16::MAX
+                    )
+                }),
+                PointSourceIds::Map(ids) => [&pointcloud.guid, &pointcloud.name]
+                    .into_iter()
+                    .flatten()
+                    .find_map(|key| ids.get(key.as_str()).copied())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Point cloud {index} ({}) has no point source ID in the mapping",
+                            pointcloud
+                                .name
+                                .as_deref()
+                                .or(pointcloud.guid.as_deref())
+                                .unwrap_or("unnamed")
+                        )
+                    }),
+            })
+            .collect()
+    }
+}
+
+impl TryFrom<&str> for PointSourceIds {
+    type Error = Error;
+
+    /// Parses `index`, or a mapping of `<guid or name>=<id>` entries separated
+    /// by commas or new lines.
+    fn try_from(value: &str) -> Result<Self> {
+        if value.trim() == "index" {
+            return Ok(PointSourceIds::Index);
+        }
+
+        let mut ids = BTreeMap::new();
+        for entry in value.split([',', '\n']).map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+
+            let (key, id) = entry
+                .rsplit_once('=')
+                .map(|(key, id)| (key.trim(), id.trim().parse::<u16>()))
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| {
+                    Error::InvalidPointSourceIds(format!(
+                        "{entry:?}, expected `<guid or name>=<id>`"
+                    ))
+                })?;
+            let id = id.map_err(|_| {
+                Error::InvalidPointSourceIds(format!(
+                    "{entry:?}, expected an ID between 0 and {}",
+                    u16::MAX
+                ))
+            })?;
+            ids.insert(String::from(key), id);
+        }
+
+        if ids.is_empty() {
+            return Err(Error::InvalidPointSourceIds(format!(
+                "{value:?}, expected `index` or `<guid or name>=<id>` entries"
+            )));
+        }
+
+        Ok(PointSourceIds::Map(ids))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn pointcloud(guid: &str, name: &str) -> PointCloud {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.guid = Some(String::from(guid));
+        pointcloud.name = Some(String::from(name));
+        pointcloud
+    }
+
+    #[test]
+    fn test_parse_point_source_ids() {
+        let ids = PointSourceIds::try_from("guid-1=7\nScan = 2 =12,").expect("Invalid mapping");
+        assert_eq!(
+            ids,
+            PointSourceIds::Map(BTreeMap::from([
+                (String::from("guid-1"), 7),
+                (String::from("Scan = 2"), 12),
+            ]))
+        );
+
+        assert!(PointSourceIds::try_from("scan=70000").is_err());
+        assert!(PointSourceIds::try_from("=3").is_err());
+        assert!(PointSourceIds::try_from("scan").is_err());
+        assert!(PointSourceIds::try_from("").is_err());
+    }
+
+    #[test]
+    fn test_resolve_point_source_ids() {
+        let first = pointcloud("guid-1", "North");
+        let second = pointcloud("guid-2", "South");
+        let pointclouds = [&first, &second];
+
+        assert_eq!(
+            PointSourceIds::Index
+                .resolve(&pointclouds)
+                .expect("Failed to resolve"),
+            [0, 1]
+        );
+
+        let ids = PointSourceIds::try_from("South=20,guid-1=10").expect("Invalid mapping");
+        assert_eq!(
+            ids.resolve(&pointclouds).expect("Failed to resolve"),
+            [10, 20]
+        );
+
+        let ids = PointSourceIds::try_from("guid-1=10").expect("Invalid mapping");
+        assert!(ids.resolve(&pointclouds).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use crate::{Error, Result};
+
+/// Tolerance of the rigidity checks of [`RigidTransform`].
+const RIGID_TOLERANCE: f64 = 1e-6;
+
+/// Whether the pose of every E57 scan is applied to its points.
+///
+/// # Example
+/// ```
+/// use e57_to_las::Pose;
+///
+/// assert_eq!(Pose::try_from("ignore").ok(), Some(Pose::Ignore));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pose {
+    /// Converts the points to the project frame by applying the `pose` of
+    /// their scan.
+    #[default]
+    Apply,
+    /// Keeps the points in the local frame of their scanner, e.g. to check a
+    /// registration.
+    Ignore,
+}
+
+impl TryFrom<&str> for Pose {
+    type Error = Error;
+
+    /// Parses `apply` or `ignore`.
+    fn try_from(value: &str) -> Result<Self> {
+        match value.trim() {
+            "apply" => Ok(Pose::Apply),
+            "ignore" => Ok(Pose::Ignore),
+            value => Err(Error::InvalidPose(format!(
+                "{value:?}, expected `apply` or `ignore`"
+            ))),
+        }
+    }
+}
+
+/// A rigid transform (rotation and translation) applied to the coordinates.
+///
+/// # Example
+/// ```
+/// use e57_to_las::RigidTransform;
+///
+/// // Shifts a local grid to UTM coordinates.
+/// let transform = RigidTransform::try_from(
+///     "1 0 0 500000
+///      0 1 0 5200000
+///      0 0 1 400
+///      0 0 0 1",
+/// );
+/// assert!(transform.is_ok());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RigidTransform {
+    rotation: [[f64; 3]; 3],
+    translation: [f64; 3],
+}
+
+impl RigidTransform {
+    /// The transform leaving positions unchanged.
+    pub(crate) const IDENTITY: Self = Self {
+        rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        translation: [0.0; 3],
+    };
+
+    /// Applies the transform to a position.
+    pub(crate) fn apply(&self, position: [f64; 3]) -> [f64; 3] {
+        let rotated = self.rotate(position);
+        [
+            rotated[0] + self.translation[0],
+            rotated[1] + self.translation[1],
+            rotated[2] + self.translation[2],
+        ]
+    }
+
+    /// Applies the rotation of the transform to a direction.
+    pub(crate) fn rotate(&self, direction: [f64; 3]) -> [f64; 3] {
+        self.rotation
+            .map(|row| row[0] * direction[0] + row[1] * direction[1] + row[2] * direction[2])
+    }
+
+    /// Returns the transform applying `self`, then `next`.
+    pub(crate) fn then(&self, next: &RigidTransform) -> Self {
+        let columns = [0, 1, 2].map(|j| next.rotate(self.rotation.map(|row| row[j])));
+
+        Self {
+            rotation: [0, 1, 2].map(|i| columns.map(|column| column[i])),
+            translation: next.apply(self.translation),
+        }
+    }
+
+    /// Returns the translation of the transform.
+    pub(crate) fn translation(&self) -> [f64; 3] {
+        self.translation
+    }
+
+    /// Returns the rotation of the transform as a unit quaternion (w, x, y, z).
+    pub(crate) fn quaternion(&self) -> [f64; 4] {
+        let r = &self.rotation;
+        let trace = r[0][0] + r[1][1] + r[2][2];
+
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [
+                0.25 * s,
+                (r[2][1] - r[1][2]) / s,
+                (r[0][2] - r[2][0]) / s,
+                (r[1][0] - r[0][1]) / s,
+            ]
+        } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+            let s = (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt() * 2.0;
+            [
+                (r[2][1] - r[1][2]) / s,
+                0.25 * s,
+                (r[0][1] + r[1][0]) / s,
+                (r[0][2] + r[2][0]) / s,
+            ]
+        } else if r[1][1] > r[2][2] {
+            let s = (1.0 + r[1][1] - r[0][0] - r[2][2]).sqrt() * 2.0;
+            [
+                (r[0][2] - r[2][0]) / s,
+                (r[0][1] + r[1][0]) / s,
+                0.25 * s,
+                (r[1][2] + r[2][1]) / s,
+            ]
+        } else {
+            let s = (1.0 + r[2][2] - r[0][0] - r[1][1]).sqrt() * 2.0;
+            [
+                (r[1][0] - r[0][1]) / s,
+                (r[0][2] + r[2][0]) / s,
+                (r[1][2] + r[2][1]) / s,
+                0.25 * s,
+            ]
+        }
+    }
+
+    /// Returns the inverse transform.
+    #[cfg_attr(not(feature = "colorize"), allow(dead_code))]
+    pub(crate) fn inverse(&self) -> Self {
+        let r = &self.rotation;
+        let rotation = [
+            [r[0][0], r[1][0], r[2][0]],
+            [r[0][1], r[1][1], r[2][1]],
+            [r[0][2], r[1][2], r[2][2]],
+        ];
+        let inverse = Self {
+            rotation,
+            translation: [0.0; 3],
+        };
+        let translation = inverse.rotate(self.translation).map(|component| -component);
+
+        Self {
+            rotation,
+            translation,
+        }
+    }
+
+    /// Returns true if the rotation matrix is orthonormal with a determinant of
+    /// 1, i.e. a proper rotation.
+    fn is_rotation(&self) -> bool {
+        let r = &self.rotation;
+        let orthonormal = (0..3).all(|i| {
+            (0..3).all(|j| {
+                let dot: f64 = (0..3).map(|k| r[i][k] * r[j][k]).sum();
+                let expected = if i == j { 1.0 } else { 0.0 };
+                (dot - expected).abs() < RIGID_TOLERANCE
+            })
+        });
+        let determinant = r[0][0] * (r[1][1] * r[2][2] - r[1][2] * r[2][1])
+            - r[0][1] * (r[1][0] * r[2][2] - r[1][2] * r[2][0])
+            + r[0][2] * (r[1][0] * r[2][1] - r[1][1] * r[2][0]);
+
+        orthonormal && (determinant - 1.0).abs() < RIGID_TOLERANCE
+    }
+}
+
+impl From<&e57::Transform> for RigidTransform {
+    /// Converts the pose of an E57 scan.
+    fn from(transform: &e57::Transform) -> Self {
+        let e57::Quaternion { w, x, y, z } = transform.rotation;
+        let translation = &transform.translation;
+
+        Self {
+            rotation: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - w * z),
+                    2.0 * (x * z + w * y),
+                ],
+                [
+                    2.0 * (x * y + w * z),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - w * x),
+                ],
+                [
+                    2.0 * (x * z - w * y),
+                    2.0 * (y * z + w * x),
+                    1.0 - 2.0 * (x * x + y * y),
+                ],
+            ],
+            translation: [translation.x, translation.y, translation.z],
+        }
+    }
+}
+
+impl TryFrom<&str> for RigidTransform {
+    type Error = Error;
+
+    /// Parses the 16 values of a row-major 4x4 matrix, separated by commas or
+    /// whitespace, and checks that it is a rigid transform.
+    fn try_from(value: &str) -> Result<Self> {
+        let values = value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<f64>().ok().filter(|value| value.is_finite()))
+            .collect::<Option<Vec<f64>>>()
+            .filter(|values| values.len() == 16)
+            .ok_or_else(|| {
+                Error::InvalidTransform(String::from(
+                    "expected the 16 values of a row-major 4x4 matrix",
+                ))
+            })?;
+
+        let row = |index: usize| {
+            [
+                values[index * 4],
+                values[index * 4 + 1],
+                values[index * 4 + 2],
+            ]
+        };
+        let transform = Self {
+            rotation: [row(0), row(1), row(2)],
+            translation: [values[3], values[7], values[11]],
+        };
+
+        let last_row = [values[12], values[13], values[14], values[15]];
+        if last_row != [0.0, 0.0, 0.0, 1.0] {
+            return Err(Error::InvalidTransform(format!(
+                "the last row must be 0 0 0 1, got {last_row:?}"
+            )));
+        }
+        if !transform.is_rotation() {
+            return Err(Error::InvalidTransform(String::from(
+                "the upper left 3x3 matrix must be a rotation, without scaling, shearing or mirroring",
+            )));
+        }
+
+        Ok(transform)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f64; 3], expected: [f64; 3]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+        }
+    }
+
+    #[test]
+    fn test_parse_pose() {
+        assert_eq!(Pose::try_from("apply").ok(), Some(Pose::Apply));
+        assert!(Pose::try_from("local").is_err());
+    }
+
+    #[test]
+    fn test_parse_rigid_transform() {
+        // A quarter turn around z, then a shift.
+        let transform = RigidTransform::try_from("0,-1,0,10, 1,0,0,20, 0,0,1,30, 0,0,0,1")
+            .expect("Invalid transform");
+        assert_close(transform.apply([1.0, 2.0, 3.0]), [8.0, 21.0, 33.0]);
+        assert_close(transform.rotate([1.0, 0.0, 0.0]), [0.0, 1.0, 0.0]);
+
+        assert!(RigidTransform::try_from("1 0 0 0 0 1 0 0 0 0 1 0").is_err());
+        // Scaling, mirroring and projective matrices are not rigid.
+        assert!(RigidTransform::try_from("2 0 0 0 0 2 0 0 0 0 2 0 0 0 0 1").is_err());
+        assert!(RigidTransform::try_from("-1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1").is_err());
+        assert!(RigidTransform::try_from("1 0 0 0 0 1 0 0 0 0 1 0 0 0 1 1").is_err());
+    }
+
+    #[test]
+    fn test_from_e57_pose() {
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let pose = e57::Transform {
+            rotation: e57::Quaternion {
+                w: half,
+                x: 0.0,
+                y: 0.0,
+                z: half,
+            },
+            translation: e57::Translation {
+                x: 10.0,
+                y: 20.0,
+                z: 30.0,
+            },
+        };
+
+        let transform = RigidTransform::from(&pose);
+        assert!(transform.is_rotation());
+        assert_close(transform.apply([1.0, 2.0, 3.0]), [8.0, 21.0, 33.0]);
+        assert_close(
+            transform.inverse().apply([8.0, 21.0, 33.0]),
+            [1.0, 2.0, 3.0],
+        );
+
+        let [w, x, y, z] = transform.quaternion();
+        assert_close([w, x, y], [half, 0.0, 0.0]);
+        assert!((z - half).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compose_transforms() {
+        let turn = RigidTransform::try_from("0 -1 0 0 1 0 0 0 0 0 1 0 0 0 0 1")
+            .expect("Invalid transform");
+        let shift = RigidTransform::try_from("1 0 0 10 0 1 0 0 0 0 1 0 0 0 0 1")
+            .expect("Invalid transform");
+
+        assert_close(turn.then(&shift).apply([1.0, 0.0, 0.0]), [10.0, 1.0, 0.0]);
+        assert_close(shift.then(&turn).apply([1.0, 0.0, 0.0]), [0.0, 11.0, 0.0]);
+        assert!(turn.then(&turn).is_rotation());
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::get_las_writer::{PointBounds, find_smallest_scale};
+use crate::octree::{Cube, OctreeNode, VoxelKey, build_octree};
+use crate::spatial_point::SpatialPoint;
+
+/// Size in bytes of a node entry in `hierarchy.bin`.
+const HIERARCHY_NODE_SIZE: usize = 22;
+/// Node type of a `hierarchy.bin` entry with children.
+const NODE_TYPE_NORMAL: u8 = 0;
+/// Node type of a `hierarchy.bin` entry without children.
+const NODE_TYPE_LEAF: u8 = 1;
+
+/// The `metadata.json` file of a Potree 2.0 octree.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Metadata {
+    version: &'static str,
+    name: String,
+    description: String,
+    points: u64,
+    projection: String,
+    hierarchy: HierarchyMetadata,
+    offset: [f64; 3],
+    scale: [f64; 3],
+    spacing: f64,
+    bounding_box: BoundingBox,
+    encoding: &'static str,
+    attributes: Vec<Attribute>,
+    /// Station positions, as in `stations.json`. Ignored by Potree itself.
+    stations: BTreeMap<usize, SpatialPoint>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HierarchyMetadata {
+    first_chunk_size: usize,
+    step_size: i32,
+    depth: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct BoundingBox {
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+/// A point attribute stored in `octree.bin`, in the order of the attributes list.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Attribute {
+    name: &'static str,
+    description: &'static str,
+    size: usize,
+    num_elements: usize,
+    element_size: usize,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    min: Vec<f64>,
+    max: Vec<f64>,
+}
+
+impl Attribute {
+    fn new(
+        name: &'static str,
+        kind: &'static str,
+        num_elements: usize,
+        element_size: usize,
+    ) -> Self {
+        Self {
+            name,
+            description: "",
+            size: num_elements * element_size,
+            num_elements,
+            element_size,
+            kind,
+            min: vec![f64::INFINITY; num_elements],
+            max: vec![f64::NEG_INFINITY; num_elements],
+        }
+    }
+
+    fn update(&mut self, values: &[f64]) {
+        for (i, value) in values.iter().enumerate() {
+            self.min[i] = self.min[i].min(*value);
+            self.max[i] = self.max[i].max(*value);
+        }
+    }
+
+    /// Replaces the untouched ranges of an attribute without values by zeros.
+    fn finish(&mut self) {
+        if self.min.iter().any(|min| min.is_infinite()) {
+            self.min.fill(0.0);
+            self.max.fill(0.0);
+        }
+    }
+}
+
+/// Writes the points as a Potree 2.0 octree in `output_dir`: `metadata.json`,
+/// `hierarchy.bin` and `octree.bin`, ready to be loaded by the Potree viewer.
+///
+/// The octree is the same as for COPC output. Every point stores its position,
+/// intensity and classification, plus its RGB color when `has_color` is set.
+/// The `stations` positions are embedded in `metadata.json`, along with the
+/// `projection` WKT, if any.
+pub(crate) fn write_potree(
+    output_dir: &Path,
+    name: String,
+    projection: String,
+    points: Vec<las::Point>,
+    bounds: PointBounds,
+    has_color: bool,
+    stations: BTreeMap<usize, SpatialPoint>,
+) -> Result<()> {
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Unable to create {}", output_dir.display()))?;
+
+    let number_of_points = points.len() as u64;
+    let cube = Cube::from_bounds(&bounds);
+    let offset = cube.min();
+    // Positions are stored relative to the cube corner, so the scale must fit
+    // the whole cube edge in i32.
+    let scale = find_smallest_scale(2.0 * cube.halfsize);
+    let nodes = build_octree(points, &cube, cube.max_depth(scale));
+
+    let mut attributes = vec![
+        Attribute::new("position", "int32", 3, 4),
+        Attribute::new("intensity", "uint16", 1, 2),
+        Attribute::new("classification", "uint8", 1, 1),
+    ];
+    if has_color {
+        attributes.push(Attribute::new("rgb", "uint16", 3, 2));
+    }
+
+    let octree_path = output_dir.join("octree.bin");
+    let file = File::create(&octree_path)
+        .with_context(|| format!("Unable to create {}", octree_path.display()))?;
+    let mut octree = BufWriter::new(file);
+
+    let order = breadth_first_order(&nodes);
+    let mut hierarchy = Vec::with_capacity(order.len() * HIERARCHY_NODE_SIZE);
+    let mut byte_offset = 0_u64;
+    let mut buffer = Vec::new();
+    let mut depth = 0;
+
+    for (key, child_mask) in order {
+        let node = &nodes[&key];
+        depth = depth.max(key.level);
+
+        buffer.clear();
+        for point in &node.points {
+            let position = [
+                ((point.x - offset.x) / scale).round(),
+                ((point.y - offset.y) / scale).round(),
+                ((point.z - offset.z) / scale).round(),
+            ];
+            for value in position {
+                buffer.extend_from_slice(&(value as i32).to_le_bytes());
+            }
+            attributes[0].update(&[point.x, point.y, point.z]);
+
+            buffer.extend_from_slice(&point.intensity.to_le_bytes());
+            attributes[1].update(&[f64::from(point.intensity)]);
+
+            let classification = u8::from(point.classification);
+            buffer.push(classification);
+            attributes[2].update(&[f64::from(classification)]);
+
+            if has_color {
+                let color = point.color.unwrap_or_default();
+                buffer.extend_from_slice(&color.red.to_le_bytes());
+                buffer.extend_from_slice(&color.green.to_le_bytes());
+                buffer.extend_from_slice(&color.blue.to_le_bytes());
+                attributes[3].update(&[
+                    f64::from(color.red),
+                    f64::from(color.green),
+                    f64::from(color.blue),
+                ]);
+            }
+        }
+        octree.write_all(&buffer)?;
+
+        let byte_size = buffer.len() as u64;
+        hierarchy.push(if child_mask == 0 {
+            NODE_TYPE_LEAF
+        } else {
+            NODE_TYPE_NORMAL
+        });
+        hierarchy.push(child_mask);
+        hierarchy.extend_from_slice(&u32::try_from(node.points.len())?.to_le_bytes());
+        hierarchy.extend_from_slice(&byte_offset.to_le_bytes());
+        hierarchy.extend_from_slice(&byte_size.to_le_bytes());
+        byte_offset += byte_size;
+    }
+    octree.flush().context("Failed to close the writer: ")?;
+
+    std::fs::write(output_dir.join("hierarchy.bin"), &hierarchy)
+        .context("Unable to write hierarchy.bin: ")?;
+
+    for attribute in &mut attributes {
+        attribute.finish();
+    }
+
+    let max = cube.max();
+    let metadata = Metadata {
+        version: "2.0",
+        name,
+        description: String::new(),
+        points: number_of_points,
+        projection,
+        hierarchy: HierarchyMetadata {
+            first_chunk_size: hierarchy.len(),
+            step_size: depth + 1,
+            depth,
+        },
+        offset: [offset.x, offset.y, offset.z],
+        scale: [scale; 3],
+        spacing: cube.spacing(),
+        bounding_box: BoundingBox {
+            min: [offset.x, offset.y, offset.z],
+            max: [max.x, max.y, max.z],
+        },
+        encoding: "DEFAULT",
+        attributes,
+        stations,
+    };
+
+    let metadata_file = File::create(output_dir.join("metadata.json"))
+        .context("Unable to create metadata.json: ")?;
+    let mut writer = BufWriter::new(metadata_file);
+    serde_json::to_writer_pretty(&mut writer, &metadata)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Returns the octree nodes in the order Potree reads `hierarchy.bin`: breadth
+/// first, children in the order of their index, along with their child mask.
+fn breadth_first_order(nodes: &BTreeMap<VoxelKey, OctreeNode>) -> Vec<(VoxelKey, u8)> {
+    let mut order = Vec::with_capacity(nodes.len());
+    let mut queue = VecDeque::new();
+    if nodes.contains_key(&VoxelKey::ROOT) {
+        queue.push_back(VoxelKey::ROOT);
+    }
+
+    while let Some(key) = queue.pop_front() {
+        let mut child_mask = 0;
+        for index in 0..8 {
+            let child = key.child(index);
+            if nodes.contains_key(&child) {
+                child_mask |= 1 << index;
+                queue.push_back(child);
+            }
+        }
+        order.push((key, child_mask));
+    }
+
+    order
+}
+
+#[cfg(test)]
+#[allow(clippy::panic, clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> las::Point {
+        las::Point {
+            x,
+            y,
+            z,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_breadth_first_order_lists_children_after_parents() {
+        let mut nodes = BTreeMap::new();
+        for key in [
+            VoxelKey::ROOT,
+            VoxelKey::ROOT.child(5),
+            VoxelKey::ROOT.child(1),
+            VoxelKey::ROOT.child(1).child(7),
+        ] {
+            nodes.insert(key, OctreeNode::default());
+        }
+
+        let order = breadth_first_order(&nodes);
+
+        assert_eq!(
+            order,
+            vec![
+                (VoxelKey::ROOT, 0b0010_0010),
+                (VoxelKey::ROOT.child(1), 0b1000_0000),
+                (VoxelKey::ROOT.child(5), 0),
+                (VoxelKey::ROOT.child(1).child(7), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_potree_files() {
+        let output_dir = tempfile::tempdir().expect("Failed to create temp output dir");
+        let points: Vec<las::Point> = (0..1000)
+            .map(|i| {
+                point(
+                    f64::from(i % 10),
+                    f64::from(i / 10 % 10),
+                    f64::from(i / 100),
+                )
+            })
+            .collect();
+        let mut bounds = PointBounds::default();
+        for point in &points {
+            bounds.update(point);
+        }
+        let mut stations = BTreeMap::new();
+        stations.insert(
+            0,
+            SpatialPoint {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+        );
+
+        write_potree(
+            output_dir.path(),
+            String::from("test"),
+            String::new(),
+            points,
+            bounds,
+            true,
+            stations,
+        )
+        .expect("Failed to write Potree octree");
+
+        let metadata: serde_json::Value = serde_json::from_reader(
+            File::open(output_dir.path().join("metadata.json")).expect("Missing metadata.json"),
+        )
+        .expect("Invalid metadata.json");
+        assert_eq!(metadata["points"], 1000);
+        assert_eq!(metadata["stations"]["0"]["z"], 3.0);
+
+        let hierarchy =
+            std::fs::read(output_dir.path().join("hierarchy.bin")).expect("Missing hierarchy.bin");
+        let octree =
+            std::fs::read(output_dir.path().join("octree.bin")).expect("Missing octree.bin");
+        assert_eq!(
+            metadata["hierarchy"]["firstChunkSize"],
+            hierarchy.len() as u64
+        );
+
+        let mut total_points = 0;
+        let mut total_bytes = 0;
+        for entry in hierarchy.chunks(HIERARCHY_NODE_SIZE) {
+            let number_of_points =
+                u32::from_le_bytes(entry[2..6].try_into().expect("Invalid entry"));
+            let byte_size = u64::from_le_bytes(entry[14..22].try_into().expect("Invalid entry"));
+            assert_eq!(byte_size, u64::from(number_of_points) * 21);
+            total_points += u64::from(number_of_points);
+            total_bytes += byte_size;
+        }
+        assert_eq!(total_points, 1000);
+        assert_eq!(total_bytes, octree.len() as u64);
+    }
+}
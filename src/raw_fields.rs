@@ -0,0 +1,276 @@
+use anyhow::{Context, Result};
+use e57::{RawValues, Record, RecordDataType, RecordName};
+
+/// A field of the point cloud prototype: its position in the raw point records
+/// and its data type.
+#[derive(Debug, Clone)]
+struct Field {
+    index: usize,
+    data_type: RecordDataType,
+}
+
+impl Field {
+    fn find(prototype: &[Record], name: RecordName) -> Option<Field> {
+        prototype
+            .iter()
+            .position(|record| record.name == name)
+            .map(|index| Field {
+                index,
+                data_type: prototype[index].data_type.clone(),
+            })
+    }
+
+    fn f64(&self, values: &RawValues) -> Result<f64> {
+        values
+            .get(self.index)
+            .context("Raw point record is shorter than the prototype")?
+            .to_f64(&self.data_type)
+            .context("Invalid raw point value: ")
+    }
+
+    fn i64(&self, values: &RawValues) -> Result<i64> {
+        values
+            .get(self.index)
+            .context("Raw point record is shorter than the prototype")?
+            .to_i64(&self.data_type)
+            .context("Invalid raw point value: ")
+    }
+}
+
+/// Returns true if the prototype has the fields of the E57 normals extension.
+pub(crate) fn has_normals(prototype: &[Record]) -> bool {
+    normal_fields(prototype).is_some()
+}
+
+/// Finds the `normalX`, `normalY` and `normalZ` fields of the E57 normals
+/// extension (<http://www.libe57.org/E57_NOR_surface_normals.txt>), whatever
+/// the namespace prefix it is registered with.
+fn normal_fields(prototype: &[Record]) -> Option<[Field; 3]> {
+    let find = |axis: &str| {
+        prototype
+            .iter()
+            .position(
+                |record| matches!(&record.name, RecordName::Unknown { name, .. } if name == axis),
+            )
+            .map(|index| Field {
+                index,
+                data_type: prototype[index].data_type.clone(),
+            })
+    };
+
+    Some([find("normalX")?, find("normalY")?, find("normalZ")?])
+}
+
+/// The values of the E57 fields of a point not exposed by the simple point
+/// reader.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct RawPoint {
+    /// Time of the point in seconds, `None` when missing or invalid.
+    pub(crate) timestamp: Option<f64>,
+    /// Zero-based index of the return of the point, if any.
+    pub(crate) return_index: Option<i64>,
+    /// Number of returns of the pulse of the point, if any.
+    pub(crate) return_count: Option<i64>,
+    /// Normal of the point, from the E57 normals extension, if any.
+    pub(crate) normal: Option<[f32; 3]>,
+    /// Whether the color of the point is flagged as invalid.
+    pub(crate) color_invalid: bool,
+}
+
+/// The E57 fields of a point cloud that the simple point reader does not
+/// expose, read from the raw point records instead.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RawFields {
+    timestamp: Option<Field>,
+    timestamp_invalid: Option<Field>,
+    return_index: Option<Field>,
+    return_count: Option<Field>,
+    normal: Option<[Field; 3]>,
+    color_invalid: Option<Field>,
+}
+
+impl RawFields {
+    pub(crate) fn new(prototype: &[Record]) -> Self {
+        Self {
+            timestamp: Field::find(prototype, RecordName::TimeStamp),
+            timestamp_invalid: Field::find(prototype, RecordName::IsTimeStampInvalid),
+            return_index: Field::find(prototype, RecordName::ReturnIndex),
+            return_count: Field::find(prototype, RecordName::ReturnCount),
+            normal: normal_fields(prototype),
+            color_invalid: Field::find(prototype, RecordName::IsColorInvalid),
+        }
+    }
+
+    /// Stops reading the color invalid state, which is only needed when the
+    /// invalid points are kept, so that it alone does not require a raw reader.
+    pub(crate) fn skip_color_invalid(&mut self) {
+        self.color_invalid = None;
+    }
+
+    /// Returns true if none of the fields are present, so that the raw point
+    /// records need not be read at all.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.timestamp.is_none()
+            && self.return_index.is_none()
+            && self.return_count.is_none()
+            && self.normal.is_none()
+            && self.color_invalid.is_none()
+    }
+
+    /// Returns true if the points have a timestamp.
+    pub(crate) fn has_timestamp(&self) -> bool {
+        self.timestamp.is_some()
+    }
+
+    /// Extracts the fields from a raw point record.
+    pub(crate) fn read(&self, values: &RawValues) -> Result<RawPoint> {
+        let mut point = RawPoint::default();
+
+        if let Some(timestamp) = &self.timestamp {
+            let invalid = match &self.timestamp_invalid {
+                Some(invalid) => invalid.i64(values)? != 0,
+                None => false,
+            };
+            if !invalid {
+                point.timestamp = Some(timestamp.f64(values)?);
+            }
+        }
+
+        if let Some(return_index) = &self.return_index {
+            point.return_index = Some(return_index.i64(values)?);
+        }
+        if let Some(return_count) = &self.return_count {
+            point.return_count = Some(return_count.i64(values)?);
+        }
+
+        if let Some([x, y, z]) = &self.normal {
+            point.normal = Some([
+                x.f64(values)? as f32,
+                y.f64(values)? as f32,
+                z.f64(values)? as f32,
+            ]);
+        }
+
+        if let Some(color_invalid) = &self.color_invalid {
+            point.color_invalid = color_invalid.i64(values)? != 0;
+        }
+
+        Ok(point)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use e57::RecordValue;
+
+    fn record(name: RecordName, data_type: RecordDataType) -> Record {
+        Record { name, data_type }
+    }
+
+    #[test]
+    fn test_read_timestamp() {
+        let prototype = vec![
+            record(RecordName::CartesianX, RecordDataType::F64),
+            record(RecordName::TimeStamp, RecordDataType::F64),
+            record(
+                RecordName::IsTimeStampInvalid,
+                RecordDataType::Integer { min: 0, max: 1 },
+            ),
+        ];
+        let fields = RawFields::new(&prototype);
+        assert!(!fields.is_empty());
+
+        let valid = vec![
+            RecordValue::Double(1.0),
+            RecordValue::Double(12.5),
+            RecordValue::Integer(0),
+        ];
+        let point = fields.read(&valid).expect("Failed to read raw point");
+        assert_eq!(point.timestamp, Some(12.5));
+
+        let invalid = vec![
+            RecordValue::Double(1.0),
+            RecordValue::Double(12.5),
+            RecordValue::Integer(1),
+        ];
+        let point = fields.read(&invalid).expect("Failed to read raw point");
+        assert_eq!(point.timestamp, None);
+    }
+
+    #[test]
+    fn test_read_returns() {
+        let prototype = vec![
+            record(
+                RecordName::ReturnIndex,
+                RecordDataType::Integer { min: 0, max: 3 },
+            ),
+            record(
+                RecordName::ReturnCount,
+                RecordDataType::Integer { min: 0, max: 4 },
+            ),
+        ];
+        let fields = RawFields::new(&prototype);
+        assert!(!fields.is_empty());
+        assert!(!fields.has_timestamp());
+
+        let point = fields
+            .read(&vec![RecordValue::Integer(1), RecordValue::Integer(3)])
+            .expect("Failed to read raw point");
+        assert_eq!(point.return_index, Some(1));
+        assert_eq!(point.return_count, Some(3));
+    }
+
+    #[test]
+    fn test_read_normal() {
+        let normal = |name: &str| {
+            record(
+                RecordName::Unknown {
+                    namespace: String::from("nor"),
+                    name: String::from(name),
+                },
+                RecordDataType::F32,
+            )
+        };
+        let prototype = vec![normal("normalX"), normal("normalY"), normal("normalZ")];
+        let fields = RawFields::new(&prototype);
+        assert!(!fields.is_empty());
+
+        let point = fields
+            .read(&vec![
+                RecordValue::Single(0.0),
+                RecordValue::Single(0.6),
+                RecordValue::Single(0.8),
+            ])
+            .expect("Failed to read raw point");
+        assert_eq!(point.normal, Some([0.0, 0.6, 0.8]));
+
+        // Incomplete normals are ignored.
+        assert!(RawFields::new(&prototype[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_read_color_invalid() {
+        let prototype = vec![record(
+            RecordName::IsColorInvalid,
+            RecordDataType::Integer { min: 0, max: 1 },
+        )];
+        let mut fields = RawFields::new(&prototype);
+        assert!(!fields.is_empty());
+
+        let point = fields
+            .read(&vec![RecordValue::Integer(1)])
+            .expect("Failed to read raw point");
+        assert!(point.color_invalid);
+
+        fields.skip_color_invalid();
+        assert!(fields.is_empty());
+    }
+
+    #[test]
+    fn test_no_raw_fields() {
+        let prototype = vec![record(RecordName::CartesianX, RecordDataType::F64)];
+        assert!(RawFields::new(&prototype).is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{Error, Result};
+
+/// How the LAS scale of an axis is chosen.
+///
+/// # Example
+/// ```
+/// use e57_to_las::Scale;
+///
+/// assert_eq!(Scale::try_from("0.0001").ok(), Some(Scale::Fixed(0.0001)));
+/// assert_eq!(
+///     Scale::parse_axes("lossless").ok(),
+///     Some([Scale::Lossless; 3])
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Scale {
+    /// 1 mm, or the smallest quantized scale fitting the bounds in i32 when
+    /// 1 mm does not.
+    #[default]
+    Auto,
+    /// A fixed scale; the conversion fails if the bounds do not fit in i32.
+    Fixed(f64),
+    /// The smallest power of ten fitting the bounds in i32, i.e. the finest
+    /// precision the bounds allow.
+    Lossless,
+}
+
+/// How the LAS offset of an axis is chosen.
+///
+/// # Example
+/// ```
+/// use e57_to_las::Offset;
+///
+/// assert_eq!(
+///     Offset::parse_axes("2600000,1200000,0").ok(),
+///     Some([Offset::Fixed(2600000.0), Offset::Fixed(1200000.0), Offset::Fixed(0.0)])
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Offset {
+    /// The midpoint of the bounds, rounded to whole meters.
+    #[default]
+    Auto,
+    /// A fixed offset, e.g. a project origin shared by several files.
+    Fixed(f64),
+}
+
+impl Scale {
+    /// Parses one scale for all axes, or three comma-separated `x,y,z` scales.
+    pub fn parse_axes(value: &str) -> Result<[Scale; 3]> {
+        parse_axes(value, |value| Error::InvalidScale(value.into()))
+    }
+}
+
+impl Offset {
+    /// Parses one offset for all axes, or three comma-separated `x,y,z` offsets.
+    pub fn parse_axes(value: &str) -> Result<[Offset; 3]> {
+        parse_axes(value, |value| Error::InvalidOffset(value.into()))
+    }
+}
+
+impl TryFrom<&str> for Scale {
+    type Error = Error;
+
+    /// Parses `auto`, `lossless` or a positive scale.
+    fn try_from(value: &str) -> Result<Self> {
+        match value.trim() {
+            "auto" => Ok(Scale::Auto),
+            "lossless" => Ok(Scale::Lossless),
+            value => value
+                .parse::<f64>()
+                .ok()
+                .filter(|scale| scale.is_finite() && *scale > 0.0)
+                .map(Scale::Fixed)
+                .ok_or_else(|| {
+                    Error::InvalidScale(format!(
+                        "{value:?}, expected `auto`, `lossless` or a positive number"
+                    ))
+                }),
+        }
+    }
+}
+
+impl TryFrom<&str> for Offset {
+    type Error = Error;
+
+    /// Parses `auto` or a finite offset.
+    fn try_from(value: &str) -> Result<Self> {
+        match value.trim() {
+            "auto" => Ok(Offset::Auto),
+            value => value
+                .parse::<f64>()
+                .ok()
+                .filter(|offset| offset.is_finite())
+                .map(Offset::Fixed)
+                .ok_or_else(|| {
+                    Error::InvalidOffset(format!("{value:?}, expected `auto` or a number"))
+                }),
+        }
+    }
+}
+
+fn parse_axes<'a, T>(value: &'a str, invalid: impl Fn(&str) -> Error) -> Result<[T; 3]>
+where
+    T: Copy + TryFrom<&'a str, Error = Error>,
+{
+    let axes = value
+        .split(',')
+        .map(T::try_from)
+        .collect::<Result<Vec<T>>>()?;
+
+    match axes[..] {
+        [all] => Ok([all; 3]),
+        [x, y, z] => Ok([x, y, z]),
+        _ => Err(invalid(&format!(
+            "{value:?}, expected one value or three comma-separated values"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scale() {
+        assert_eq!(Scale::try_from("auto").ok(), Some(Scale::Auto));
+        assert_eq!(Scale::try_from(" 0.001 ").ok(), Some(Scale::Fixed(0.001)));
+        assert!(Scale::try_from("0").is_err());
+        assert!(Scale::try_from("-0.01").is_err());
+        assert!(Scale::try_from("fine").is_err());
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(Offset::try_from("auto").ok(), Some(Offset::Auto));
+        assert_eq!(Offset::try_from("-12.5").ok(), Some(Offset::Fixed(-12.5)));
+        assert!(Offset::try_from("lossless").is_err());
+        assert!(Offset::try_from("inf").is_err());
+    }
+
+    #[test]
+    fn test_parse_axes() {
+        assert_eq!(
+            Scale::parse_axes("0.0001,0.0001,auto").ok(),
+            Some([Scale::Fixed(0.0001), Scale::Fixed(0.0001), Scale::Auto])
+        );
+        assert_eq!(Offset::parse_axes("auto").ok(), Some([Offset::Auto; 3]));
+        assert!(Scale::parse_axes("0.001,0.001").is_err());
+        assert!(Offset::parse_axes("1,2,3,4").is_err());
+    }
+}
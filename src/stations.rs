@@ -1,6 +1,8 @@
 use crate::spatial_point::SpatialPoint;
+use crate::{ConvertOptions, Error, Pose, Result as CrateResult, RigidTransform};
 use anyhow::Result;
 use e57::PointCloud;
+use serde::Serialize;
 use std::{
     collections::BTreeMap,
     fs::File,
@@ -8,30 +10,576 @@ use std::{
     path::Path,
 };
 
-pub(crate) fn save_stations<P: AsRef<Path>>(
-    output_path: P,
+/// Version of the schema of the [`StationsFormat::Json`] stations file.
+const STATIONS_SCHEMA_VERSION: u32 = 2;
+
+/// A format of the stations file.
+///
+/// # Example
+/// ```
+/// use e57_to_las::StationsFormat;
+///
+/// assert_eq!(StationsFormat::try_from("json").ok(), Some(StationsFormat::Json));
+/// assert_eq!(
+///     StationsFormat::parse_list("json,geojson,csv").ok(),
+///     Some(vec![StationsFormat::Json, StationsFormat::GeoJson, StationsFormat::Csv])
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StationsFormat {
+    /// A map from the point cloud index to the station position, e.g.
+    /// `{"0":{"x":1.0,"y":2.0,"z":3.0}}`, along with the path of the LAS file
+    /// of the station when each one has its own (`"file":"las/0.las"`).
+    #[default]
+    Legacy,
+    /// A versioned document (`{"version":2,"stations":[...]}`) describing
+    /// every station: name, GUID, pose, acquisition dates, sensor, point count
+    /// and bounds.
+    Json,
+    /// A GeoJSON FeatureCollection (`stations.geojson`) of Point features
+    /// whose properties are the stations of [`StationsFormat::Json`], with the
+    /// CRS of the output files, if it has an EPSG code.
+    GeoJson,
+    /// A CSV file (`stations.csv`) with one row per station.
+    Csv,
+}
+
+impl StationsFormat {
+    /// Parses a comma-separated list of formats. `legacy` and `json` both
+    /// write `stations.json`, so they cannot be combined.
+    pub fn parse_list(value: &str) -> CrateResult<Vec<StationsFormat>> {
+        let mut formats = Vec::new();
+        for format in value.split(',').map(StationsFormat::try_from) {
+            let format = format?;
+            if !formats.contains(&format) {
+                formats.push(format);
+            }
+        }
+
+        if formats.is_empty() {
+            return Err(Error::InvalidStationsFormat(String::from(
+                "expected at least one format",
+            )));
+        }
+        if formats.contains(&StationsFormat::Legacy) && formats.contains(&StationsFormat::Json) {
+            return Err(Error::InvalidStationsFormat(String::from(
+                "`legacy` and `json` cannot be combined, both write stations.json",
+            )));
+        }
+
+        Ok(formats)
+    }
+
+    /// Returns the name of the file written in this format.
+    fn file_name(&self) -> &'static str {
+        match self {
+            StationsFormat::Legacy | StationsFormat::Json => "stations.json",
+            StationsFormat::GeoJson => "stations.geojson",
+            StationsFormat::Csv => "stations.csv",
+        }
+    }
+}
+
+impl TryFrom<&str> for StationsFormat {
+    type Error = Error;
+
+    /// Parses `legacy`, `json`, `geojson` or `csv`.
+    fn try_from(value: &str) -> CrateResult<Self> {
+        match value.trim() {
+            "legacy" => Ok(StationsFormat::Legacy),
+            "json" => Ok(StationsFormat::Json),
+            "geojson" => Ok(StationsFormat::GeoJson),
+            "csv" => Ok(StationsFormat::Csv),
+            value => Err(Error::InvalidStationsFormat(format!(
+                "{value:?}, expected `legacy`, `json`, `geojson` or `csv`"
+            ))),
+        }
+    }
+}
+
+/// A station of the versioned stations file.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Station<'a> {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guid: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    /// Whether the E57 point cloud has a pose; without one, the station is at
+    /// the origin of the project frame.
+    has_pose: bool,
+    /// The station position, in the frame of the converted points.
+    position: SpatialPoint,
+    /// The rotation (w, x, y, z) from the frame of the scanner to the frame of
+    /// the converted points.
+    rotation: [f64; 4],
+    /// GPS time (seconds since 1980-01-06) of the acquisition start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquisition_start: Option<f64>,
+    /// GPS time of the acquisition end.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquisition_end: Option<f64>,
+    sensor: Sensor<'a>,
+    /// Number of points of the E57 point cloud, including the invalid ones.
+    point_count: u64,
+    /// The E57 bounds of the point cloud, in the frame of the converted points.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bounds: Option<StationBounds>,
+    /// The path of the LAS file of the station, relative to the output
+    /// directory, when each station is converted to its own file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Sensor<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vendor: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    serial_number: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hardware_version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    software_version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    firmware_version: Option<&'a str>,
+}
+
+/// A station of the legacy stations file.
+#[derive(Debug, Serialize)]
+struct LegacyStation {
+    #[serde(flatten)]
+    position: SpatialPoint,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct StationBounds {
+    min: SpatialPoint,
+    max: SpatialPoint,
+}
+
+#[derive(Debug, Serialize)]
+struct StationsFile<'a> {
+    version: u32,
+    stations: Vec<Station<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct FeatureCollection<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// The named CRS of the 2008 GeoJSON specification, since RFC 7946 only
+    /// allows WGS 84 coordinates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crs: Option<serde_json::Value>,
+    features: Vec<Feature<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Feature<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    geometry: serde_json::Value,
+    properties: Station<'a>,
+}
+
+/// Columns of the CSV stations file.
+const CSV_HEADER: &str = "index,name,guid,has_pose,x,y,z,qw,qx,qy,qz,acquisition_start,acquisition_end,sensor_vendor,sensor_model,sensor_serial_number,point_count,file";
+
+/// Returns the transform from the frame of the scanner of a point cloud to the
+/// frame of the converted points: its pose (unless it has none or with
+/// [`Pose::Ignore`]), followed by `options.transform`.
+fn station_transform(pointcloud: &PointCloud, options: &ConvertOptions) -> RigidTransform {
+    let pose = match (options.pose, &pointcloud.transform) {
+        (Pose::Apply, Some(transform)) => RigidTransform::from(transform),
+        _ => RigidTransform::IDENTITY,
+    };
+
+    match &options.transform {
+        Some(transform) => pose.then(transform),
+        None => pose,
+    }
+}
+
+/// Returns the axis-aligned box enclosing the E57 bounds of a point cloud (in
+/// the frame of its scanner) once moved by `transform`.
+fn station_bounds(pointcloud: &PointCloud, transform: &RigidTransform) -> Option<StationBounds> {
+    let bounds = pointcloud.get_cartesian_bounds()?;
+    let x = [bounds.x_min?, bounds.x_max?];
+    let y = [bounds.y_min?, bounds.y_max?];
+    let z = [bounds.z_min?, bounds.z_max?];
+
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for corner in (0..8).map(|i| [x[i & 1], y[(i >> 1) & 1], z[(i >> 2) & 1]]) {
+        let corner = transform.apply(corner);
+        for axis in 0..3 {
+            min[axis] = min[axis].min(corner[axis]);
+            max[axis] = max[axis].max(corner[axis]);
+        }
+    }
+
+    let [x, y, z] = min;
+    let min = SpatialPoint { x, y, z };
+    let [x, y, z] = max;
+    let max = SpatialPoint { x, y, z };
+
+    Some(StationBounds { min, max })
+}
+
+fn station<'a>(
+    index: usize,
+    pointcloud: &'a PointCloud,
+    file: Option<&str>,
+    options: &ConvertOptions,
+) -> Station<'a> {
+    let transform = station_transform(pointcloud, options);
+    let [x, y, z] = transform.translation();
+
+    Station {
+        index,
+        name: pointcloud.name.as_deref(),
+        guid: pointcloud.guid.as_deref(),
+        description: pointcloud.description.as_deref(),
+        has_pose: pointcloud.transform.is_some(),
+        position: SpatialPoint { x, y, z },
+        rotation: transform.quaternion(),
+        acquisition_start: pointcloud.acquisition_start.as_ref().map(|d| d.gps_time),
+        acquisition_end: pointcloud.acquisition_end.as_ref().map(|d| d.gps_time),
+        sensor: Sensor {
+            vendor: pointcloud.sensor_vendor.as_deref(),
+            model: pointcloud.sensor_model.as_deref(),
+            serial_number: pointcloud.sensor_serial.as_deref(),
+            hardware_version: pointcloud.sensor_hw_version.as_deref(),
+            software_version: pointcloud.sensor_sw_version.as_deref(),
+            firmware_version: pointcloud.sensor_fw_version.as_deref(),
+        },
+        point_count: pointcloud.records,
+        bounds: station_bounds(pointcloud, &transform),
+        file: file.map(|file| format!("las/{file}")),
+    }
+}
+
+/// Returns the position of every station, indexed like the point clouds and
+/// taken from the translation of their pose (the origin when they have none or
+/// with [`Pose::Ignore`]), in the frame of the converted points.
+pub(crate) fn station_positions(
     pointclouds: &[PointCloud],
-) -> Result<()> {
-    let stations: BTreeMap<usize, SpatialPoint> = pointclouds
+    options: &ConvertOptions,
+) -> BTreeMap<usize, SpatialPoint> {
+    pointclouds
         .iter()
         .enumerate()
         .map(|(index, pc)| {
-            let (x, y, z) = pc
-                .transform
-                .as_ref()
-                .map(|t| (t.translation.x, t.translation.y, t.translation.z))
-                .unwrap_or((0.0, 0.0, 0.0));
-
+            let [x, y, z] = station_transform(pc, options).translation();
             let station_point = SpatialPoint { x, y, z };
 
             (index, station_point)
         })
-        .collect();
+        .collect()
+}
+
+fn legacy_stations(
+    pointclouds: &[PointCloud],
+    files: Option<&[String]>,
+    options: &ConvertOptions,
+) -> BTreeMap<usize, LegacyStation> {
+    station_positions(pointclouds, options)
+        .into_iter()
+        .map(|(index, position)| {
+            let file = files
+                .and_then(|files| files.get(index))
+                .map(|file| format!("las/{file}"));
+            (index, LegacyStation { position, file })
+        })
+        .collect()
+}
+
+fn stations<'a>(
+    pointclouds: &'a [PointCloud],
+    files: Option<&[String]>,
+    options: &ConvertOptions,
+) -> Vec<Station<'a>> {
+    pointclouds
+        .iter()
+        .enumerate()
+        .map(|(index, pointcloud)| {
+            let file = files.and_then(|files| files.get(index)).map(String::as_str);
+            station(index, pointcloud, file, options)
+        })
+        .collect()
+}
+
+fn feature_collection<'a>(
+    pointclouds: &'a [PointCloud],
+    files: Option<&[String]>,
+    options: &ConvertOptions,
+) -> FeatureCollection<'a> {
+    let crs = options.crs.as_ref().and_then(|crs| crs.epsg()).map(|code| {
+        serde_json::json!({
+            "type": "name",
+            "properties": { "name": format!("urn:ogc:def:crs:EPSG::{code}") },
+        })
+    });
+
+    FeatureCollection {
+        kind: "FeatureCollection",
+        crs,
+        features: stations(pointclouds, files, options)
+            .into_iter()
+            .map(|station| Feature {
+                kind: "Feature",
+                geometry: serde_json::json!({
+                    "type": "Point",
+                    "coordinates": [station.position.x, station.position.y, station.position.z],
+                }),
+                properties: station,
+            })
+            .collect(),
+    }
+}
+
+/// Quotes a CSV field when it holds a separator, a quote or a line break.
+fn csv_field(value: Option<&str>) -> String {
+    let value = value.unwrap_or_default();
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn csv_row(station: &Station) -> String {
+    let optional = |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+    let [qw, qx, qy, qz] = station.rotation;
+
+    [
+        station.index.to_string(),
+        csv_field(station.name),
+        csv_field(station.guid),
+        station.has_pose.to_string(),
+        station.position.x.to_string(),
+        station.position.y.to_string(),
+        station.position.z.to_string(),
+        qw.to_string(),
+        qx.to_string(),
+        qy.to_string(),
+        qz.to_string(),
+        optional(station.acquisition_start),
+        optional(station.acquisition_end),
+        csv_field(station.sensor.vendor),
+        csv_field(station.sensor.model),
+        csv_field(station.sensor.serial_number),
+        station.point_count.to_string(),
+        csv_field(station.file.as_deref()),
+    ]
+    .join(",")
+}
+
+/// Writes the stations file of every format of `options.stations_formats` in
+/// the output directory. `files` holds the name of the LAS file of every point
+/// cloud, when they are converted as distinct stations, so that the stations
+/// point to their file.
+pub(crate) fn save_stations<P: AsRef<Path>>(
+    output_path: P,
+    pointclouds: &[PointCloud],
+    files: Option<&[String]>,
+    options: &ConvertOptions,
+) -> Result<()> {
+    for format in &options.stations_formats {
+        let stations_file = File::create(output_path.as_ref().join(format.file_name()))?;
+        let mut writer = BufWriter::new(stations_file);
 
-    let stations_file = File::create(output_path.as_ref().join("stations.json"))?;
-    let mut writer = BufWriter::new(stations_file);
-    serde_json::to_writer(&mut writer, &stations)?;
-    writer.flush()?;
+        match format {
+            StationsFormat::Legacy => {
+                serde_json::to_writer(&mut writer, &legacy_stations(pointclouds, files, options))?;
+            }
+            StationsFormat::Json => {
+                let stations = StationsFile {
+                    version: STATIONS_SCHEMA_VERSION,
+                    stations: stations(pointclouds, files, options),
+                };
+                serde_json::to_writer(&mut writer, &stations)?;
+            }
+            StationsFormat::GeoJson => {
+                serde_json::to_writer(
+                    &mut writer,
+                    &feature_collection(pointclouds, files, options),
+                )?;
+            }
+            StationsFormat::Csv => {
+                writeln!(writer, "{CSV_HEADER}")?;
+                for station in stations(pointclouds, files, options) {
+                    writeln!(writer, "{}", csv_row(&station))?;
+                }
+            }
+        }
+        writer.flush()?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn posed_pointcloud() -> PointCloud {
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let mut pointcloud = PointCloud::default();
+        pointcloud.name = Some(String::from("Scan 01"));
+        pointcloud.records = 42;
+        pointcloud.transform = Some(e57::Transform {
+            rotation: e57::Quaternion {
+                w: half,
+                x: 0.0,
+                y: 0.0,
+                z: half,
+            },
+            translation: e57::Translation {
+                x: 10.0,
+                y: 20.0,
+                z: 30.0,
+            },
+        });
+        pointcloud.cartesian_bounds = Some(e57::CartesianBounds {
+            x_min: Some(-1.0),
+            x_max: Some(2.0),
+            y_min: Some(-3.0),
+            y_max: Some(4.0),
+            z_min: Some(0.0),
+            z_max: Some(1.0),
+        });
+        pointcloud
+    }
+
+    #[test]
+    fn test_parse_stations_format() {
+        assert_eq!(
+            StationsFormat::try_from("legacy").ok(),
+            Some(StationsFormat::Legacy)
+        );
+        assert!(StationsFormat::try_from("yaml").is_err());
+    }
+
+    #[test]
+    fn test_versioned_station() {
+        let pointcloud = posed_pointcloud();
+        let station = serde_json::to_value(station(
+            3,
+            &pointcloud,
+            Some("3.las"),
+            &ConvertOptions::default(),
+        ))
+        .expect("Failed to serialize station");
+
+        assert_eq!(station["index"], 3);
+        assert_eq!(station["name"], "Scan 01");
+        assert_eq!(station["hasPose"], true);
+        assert_eq!(station["position"]["x"], 10.0);
+        assert_eq!(station["pointCount"], 42);
+        assert_eq!(station["file"], "las/3.las");
+        // The quarter turn around z swaps the x and y extents.
+        let coordinate = |bound: &str, axis: &str| {
+            station["bounds"][bound][axis]
+                .as_f64()
+                .expect("Missing bounds")
+        };
+        assert!((coordinate("min", "x") - 6.0).abs() < 1e-9);
+        assert!((coordinate("max", "y") - 22.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_stations_formats() {
+        assert_eq!(
+            StationsFormat::parse_list("geojson, csv,geojson").ok(),
+            Some(vec![StationsFormat::GeoJson, StationsFormat::Csv])
+        );
+        assert!(StationsFormat::parse_list("legacy,json").is_err());
+        assert!(StationsFormat::parse_list("json,kml").is_err());
+    }
+
+    #[test]
+    fn test_geojson_stations() {
+        let pointclouds = [posed_pointcloud()];
+        let options = ConvertOptions {
+            crs: Some(crate::Crs::Epsg(2056)),
+            ..Default::default()
+        };
+        let collection = serde_json::to_value(feature_collection(&pointclouds, None, &options))
+            .expect("Failed to serialize stations");
+
+        assert_eq!(collection["type"], "FeatureCollection");
+        assert_eq!(
+            collection["crs"]["properties"]["name"],
+            "urn:ogc:def:crs:EPSG::2056"
+        );
+        let feature = &collection["features"][0];
+        assert_eq!(feature["geometry"]["type"], "Point");
+        assert_eq!(
+            feature["geometry"]["coordinates"],
+            serde_json::json!([10.0, 20.0, 30.0])
+        );
+        assert_eq!(feature["properties"]["name"], "Scan 01");
+        assert!(feature["properties"].get("file").is_none());
+    }
+
+    #[test]
+    fn test_csv_stations() {
+        let mut pointcloud = posed_pointcloud();
+        pointcloud.name = Some(String::from("Scan \"A\", north"));
+        let row = csv_row(&station(
+            0,
+            &pointcloud,
+            Some("Scan A, north.las"),
+            &ConvertOptions::default(),
+        ));
+
+        assert!(row.starts_with("0,\"Scan \"\"A\"\", north\",,true,10,20,30,"));
+        assert!(row.ends_with(",,,,,,42,\"las/Scan A, north.las\""));
+        assert_eq!(
+            row.matches(',').count() - 2,
+            CSV_HEADER.matches(',').count()
+        );
+    }
+
+    #[test]
+    fn test_legacy_stations() {
+        let pointclouds = [posed_pointcloud()];
+        let options = ConvertOptions::default();
+
+        let stations = serde_json::to_value(legacy_stations(&pointclouds, None, &options))
+            .expect("Failed to serialize stations");
+        assert_eq!(
+            stations,
+            serde_json::json!({ "0": { "x": 10.0, "y": 20.0, "z": 30.0 } })
+        );
+
+        let files = [String::from("Scan 01.las")];
+        let stations = serde_json::to_value(legacy_stations(&pointclouds, Some(&files), &options))
+            .expect("Failed to serialize stations");
+        assert_eq!(stations["0"]["x"], 10.0);
+        assert_eq!(stations["0"]["file"], "las/Scan 01.las");
+    }
+
+    #[test]
+    fn test_station_without_pose() {
+        let pointcloud = PointCloud::default();
+        let station =
+            serde_json::to_value(station(0, &pointcloud, None, &ConvertOptions::default()))
+                .expect("Failed to serialize station");
+
+        assert_eq!(station["hasPose"], false);
+        assert_eq!(station["rotation"], serde_json::json!([1.0, 0.0, 0.0, 0.0]));
+        assert!(station.get("bounds").is_none());
+    }
+}
@@ -0,0 +1,56 @@
+#![allow(clippy::expect_used)]
+
+use std::{fs::File, path::Path};
+
+use e57::{E57Writer, PointCloudWriter, Record, RecordDataType, RecordName, RecordValue};
+
+/// The guid of the E57 files written by [`write_e57`].
+pub(crate) const FILE_GUID: &str = "3f2504e0-4f89-11d3-9a0c-0305e82c3301";
+/// The guid of the point cloud of the E57 files written by [`write_e57`].
+pub(crate) const POINTCLOUD_GUID: &str = "a0000000-0000-0000-0000-000000000001";
+
+/// Returns the records of cartesian coordinates stored as doubles.
+pub(crate) fn cartesian_prototype() -> Vec<Record> {
+    [
+        RecordName::CartesianX,
+        RecordName::CartesianY,
+        RecordName::CartesianZ,
+    ]
+    .map(|name| Record {
+        name,
+        data_type: RecordDataType::F64,
+    })
+    .to_vec()
+}
+
+/// Writes an E57 file with one point cloud of `prototype` holding `points`.
+pub(crate) fn write_e57(path: &Path, prototype: Vec<Record>, points: Vec<Vec<RecordValue>>) {
+    write_e57_with(path, prototype, points, |_| {}, |_| {});
+}
+
+/// Writes an E57 file like [`write_e57`], calling `edit_pointcloud` on the
+/// point cloud before its points are added, and `edit_file` on the file once
+/// the point cloud is written, e.g. to add images.
+pub(crate) fn write_e57_with(
+    path: &Path,
+    prototype: Vec<Record>,
+    points: Vec<Vec<RecordValue>>,
+    edit_pointcloud: impl FnOnce(&mut PointCloudWriter<'_, File>),
+    edit_file: impl FnOnce(&mut E57Writer<File>),
+) {
+    let mut writer = E57Writer::from_file(path, FILE_GUID).expect("Failed to create E57 writer");
+    let mut pointcloud_writer = writer
+        .add_pointcloud(POINTCLOUD_GUID, prototype)
+        .expect("Failed to add point cloud");
+    edit_pointcloud(&mut pointcloud_writer);
+    for point in points {
+        pointcloud_writer
+            .add_point(point)
+            .expect("Failed to add point");
+    }
+    pointcloud_writer
+        .finalize()
+        .expect("Failed to finalize point cloud");
+    edit_file(&mut writer);
+    writer.finalize().expect("Failed to finalize E57 file");
+}
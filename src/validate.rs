@@ -0,0 +1,563 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::Context;
+use e57::{E57Reader, Header, PointCloud, Projection, RecordDataType, RecordName};
+use serde::Serialize;
+
+/// Size in bytes of the E57 file header.
+const HEADER_SIZE: u64 = 48;
+/// Size in bytes of the checksum ending every E57 page.
+const CHECKSUM_SIZE: u64 = 4;
+/// The E57 format name, as written in the XML section.
+const FORMAT_NAME: &str = "ASTM E57 3D Imaging Data File";
+
+/// A problem found in an E57 file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationIssue {
+    /// Index of the point cloud the problem was found in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan: Option<usize>,
+    /// Index of the image the problem was found in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<usize>,
+    /// Physical byte offset of the problem in the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn file(offset: Option<u64>, message: impl Into<String>) -> Self {
+        Self {
+            scan: None,
+            image: None,
+            offset,
+            message: message.into(),
+        }
+    }
+
+    fn scan(scan: usize, offset: Option<u64>, message: impl Into<String>) -> Self {
+        Self {
+            scan: Some(scan),
+            ..Self::file(offset, message)
+        }
+    }
+
+    fn image(image: usize, offset: Option<u64>, message: impl Into<String>) -> Self {
+        Self {
+            image: Some(image),
+            ..Self::file(offset, message)
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(scan) = self.scan {
+            write!(f, "scan {scan}: ")?;
+        }
+        if let Some(image) = self.image {
+            write!(f, "image {image}: ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(offset) = self.offset {
+            write!(f, " (at byte {offset})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the integrity of the E57 file at `path` without converting it: the
+/// header, the checksum of every page, the XML section, the prototype of every
+/// point cloud, its declared and actual number of records, and the image
+/// blobs. Returns every problem found, or an empty list for a valid file.
+///
+/// # Example
+/// ```no_run
+/// let issues = e57_to_las::validate_file("path/to/input.e57").expect("Failed to read E57 file");
+/// for issue in &issues {
+///     eprintln!("{issue}");
+/// }
+/// ```
+pub fn validate_file(path: impl AsRef<Path>) -> crate::Result<Vec<ValidationIssue>> {
+    let path = path.as_ref();
+    let open = || {
+        File::open(path)
+            .map(BufReader::new)
+            .with_context(|| format!("Unable to open e57 file {}", path.display()))
+    };
+
+    let mut reader = open()?;
+    let file_size = reader
+        .seek(SeekFrom::End(0))
+        .context("Unable to read file")?;
+    reader.rewind().context("Unable to read file")?;
+
+    let mut issues = Vec::new();
+    if file_size < HEADER_SIZE {
+        issues.push(ValidationIssue::file(
+            Some(0),
+            format!("File of {file_size} bytes is too small for an E57 header"),
+        ));
+        return Ok(issues);
+    }
+
+    let header = match Header::read(&mut reader) {
+        Ok(header) => header,
+        Err(error) => {
+            issues.push(ValidationIssue::file(
+                Some(0),
+                format!("Invalid header: {error}"),
+            ));
+            return Ok(issues);
+        }
+    };
+    issues.extend(check_header(&header, file_size));
+    issues.extend(check_pages(&mut reader, header.page_size, file_size)?);
+
+    let mut e57_reader = match E57Reader::new(open()?) {
+        Ok(e57_reader) => e57_reader,
+        Err(error) => {
+            issues.push(ValidationIssue::file(
+                Some(header.phys_xml_offset),
+                format!("Invalid XML section: {error}"),
+            ));
+            return Ok(issues);
+        }
+    };
+
+    if e57_reader.format_name() != FORMAT_NAME {
+        issues.push(ValidationIssue::file(
+            Some(header.phys_xml_offset),
+            format!("Unexpected format name {:?}", e57_reader.format_name()),
+        ));
+    }
+    if e57_reader.guid().trim().is_empty() {
+        issues.push(ValidationIssue::file(
+            Some(header.phys_xml_offset),
+            "Missing file GUID",
+        ));
+    }
+
+    let pointclouds = e57_reader.pointclouds();
+    for (index, pointcloud) in pointclouds.iter().enumerate() {
+        let duplicated = pointcloud.guid.is_some()
+            && pointclouds[..index]
+                .iter()
+                .any(|other| other.guid == pointcloud.guid);
+        if duplicated {
+            issues.push(ValidationIssue::scan(
+                index,
+                None,
+                format!(
+                    "Duplicated GUID {}",
+                    pointcloud.guid.as_deref().unwrap_or("")
+                ),
+            ));
+        }
+
+        let prototype_issues = check_prototype(pointcloud);
+        let prototype_valid = prototype_issues.is_empty();
+        issues.extend(
+            prototype_issues
+                .into_iter()
+                .map(|message| ValidationIssue::scan(index, None, message)),
+        );
+
+        if pointcloud.file_offset >= file_size {
+            issues.push(ValidationIssue::scan(
+                index,
+                Some(pointcloud.file_offset),
+                format!("Binary section starts after the end of the file ({file_size} bytes)"),
+            ));
+        } else if prototype_valid {
+            issues.extend(check_records(&mut e57_reader, index, pointcloud));
+        }
+    }
+
+    for (index, image) in e57_reader.images().iter().enumerate() {
+        if let Some(guid) = &image.pointcloud_guid
+            && !pointclouds
+                .iter()
+                .any(|pointcloud| pointcloud.guid.as_ref() == Some(guid))
+        {
+            issues.push(ValidationIssue::image(
+                index,
+                None,
+                format!("References the unknown point cloud {guid}"),
+            ));
+        }
+
+        for blob in image_blobs(image) {
+            if let Err(error) = e57_reader.blob(blob, &mut std::io::sink()) {
+                issues.push(ValidationIssue::image(
+                    index,
+                    Some(blob.offset),
+                    format!("Unable to read blob of {} bytes: {error}", blob.length),
+                ));
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Checks the header fields against the size of the file.
+fn check_header(header: &Header, file_size: u64) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    if header.phys_length != file_size {
+        issues.push(ValidationIssue::file(
+            Some(16),
+            format!(
+                "Header declares a file length of {} bytes, but the file has {file_size} bytes",
+                header.phys_length
+            ),
+        ));
+    }
+    if header.phys_xml_offset >= file_size {
+        issues.push(ValidationIssue::file(
+            Some(24),
+            format!(
+                "XML section offset {} is after the end of the file",
+                header.phys_xml_offset
+            ),
+        ));
+    }
+    if header.xml_length == 0 {
+        issues.push(ValidationIssue::file(Some(32), "Empty XML section"));
+    }
+    issues
+}
+
+/// Checks the CRC-32C checksum of every page, stored in big endian order in
+/// its last 4 bytes.
+fn check_pages<R: Read + Seek>(
+    reader: &mut R,
+    page_size: u64,
+    file_size: u64,
+) -> anyhow::Result<Vec<ValidationIssue>> {
+    let mut issues = Vec::new();
+    if !file_size.is_multiple_of(page_size) {
+        issues.push(ValidationIssue::file(
+            Some(file_size - file_size % page_size),
+            format!("File size {file_size} is not a multiple of the page size {page_size}"),
+        ));
+    }
+
+    reader.rewind()?;
+    let mut page = vec![0_u8; page_size as usize];
+    let data_size = (page_size - CHECKSUM_SIZE) as usize;
+    for index in 0..file_size / page_size {
+        reader
+            .read_exact(&mut page)
+            .with_context(|| format!("Unable to read page {index}"))?;
+        let expected = u32::from_be_bytes([
+            page[data_size],
+            page[data_size + 1],
+            page[data_size + 2],
+            page[data_size + 3],
+        ]);
+        let actual = crc32c(&page[..data_size]);
+        if actual != expected {
+            issues.push(ValidationIssue::file(
+                Some(index * page_size),
+                format!(
+                    "Checksum mismatch in page {index}: expected {expected:#010x}, found {actual:#010x}"
+                ),
+            ));
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Checks that the prototype of a point cloud describes complete coordinates
+/// and attributes with sensible data types.
+fn check_prototype(pointcloud: &PointCloud) -> Vec<String> {
+    let prototype = &pointcloud.prototype;
+    let has = |name: &RecordName| prototype.iter().any(|record| &record.name == name);
+    let mut issues = Vec::new();
+
+    for (index, record) in prototype.iter().enumerate() {
+        if prototype[..index]
+            .iter()
+            .any(|other| other.name == record.name)
+        {
+            issues.push(format!("Duplicated prototype field {:?}", record.name));
+        }
+
+        let valid = match record.data_type {
+            RecordDataType::Integer { min, max } => min <= max,
+            RecordDataType::ScaledInteger {
+                min, max, scale, ..
+            } => min <= max && scale.is_finite() && scale != 0.0,
+            RecordDataType::Single {
+                min: Some(min),
+                max: Some(max),
+            } => min <= max,
+            RecordDataType::Double {
+                min: Some(min),
+                max: Some(max),
+            } => min <= max,
+            _ => true,
+        };
+        if !valid {
+            issues.push(format!(
+                "Invalid data type {:?} for prototype field {:?}",
+                record.data_type, record.name
+            ));
+        }
+    }
+
+    let groups = [
+        (
+            "cartesian coordinates",
+            vec![
+                RecordName::CartesianX,
+                RecordName::CartesianY,
+                RecordName::CartesianZ,
+            ],
+        ),
+        (
+            "spherical coordinates",
+            vec![
+                RecordName::SphericalRange,
+                RecordName::SphericalAzimuth,
+                RecordName::SphericalElevation,
+            ],
+        ),
+        (
+            "color",
+            vec![
+                RecordName::ColorRed,
+                RecordName::ColorGreen,
+                RecordName::ColorBlue,
+            ],
+        ),
+    ];
+    for (group, names) in &groups {
+        let present = names.iter().filter(|name| has(name)).count();
+        if present != 0 && present != names.len() {
+            issues.push(format!("Incomplete {group} in the prototype"));
+        }
+    }
+
+    let dependencies = [
+        (RecordName::CartesianInvalidState, RecordName::CartesianX),
+        (
+            RecordName::SphericalInvalidState,
+            RecordName::SphericalRange,
+        ),
+        (RecordName::IsIntensityInvalid, RecordName::Intensity),
+        (RecordName::IsColorInvalid, RecordName::ColorRed),
+        (RecordName::IsTimeStampInvalid, RecordName::TimeStamp),
+        (RecordName::RowIndex, RecordName::ColumnIndex),
+        (RecordName::ColumnIndex, RecordName::RowIndex),
+    ];
+    for (field, required) in &dependencies {
+        if has(field) && !has(required) {
+            issues.push(format!(
+                "Prototype field {field:?} without the field {required:?}"
+            ));
+        }
+    }
+
+    if !has(&RecordName::CartesianX) && !has(&RecordName::SphericalRange) {
+        issues.push(String::from("The prototype has no coordinates"));
+    }
+
+    issues
+}
+
+/// Reads every record of a point cloud and compares their number with the
+/// declared one.
+fn check_records<R: Read + Seek>(
+    e57_reader: &mut E57Reader<R>,
+    index: usize,
+    pointcloud: &PointCloud,
+) -> Vec<ValidationIssue> {
+    let offset = Some(pointcloud.file_offset);
+    let reader = match e57_reader.pointcloud_raw(pointcloud) {
+        Ok(reader) => reader,
+        Err(error) => {
+            return vec![ValidationIssue::scan(
+                index,
+                offset,
+                format!("Unable to read the binary section: {error}"),
+            )];
+        }
+    };
+
+    let mut records = 0_u64;
+    for record in reader {
+        if let Err(error) = record {
+            return vec![ValidationIssue::scan(
+                index,
+                offset,
+                format!("Unable to read record {records}: {error}"),
+            )];
+        }
+        records += 1;
+    }
+
+    if records != pointcloud.records {
+        return vec![ValidationIssue::scan(
+            index,
+            offset,
+            format!(
+                "Declares {} records, but its binary section holds {records}",
+                pointcloud.records
+            ),
+        )];
+    }
+    Vec::new()
+}
+
+/// Returns the blobs of an image: its data and mask, and its visual reference.
+fn image_blobs(image: &e57::Image) -> Vec<&e57::Blob> {
+    let mut blobs = Vec::new();
+    match &image.projection {
+        Some(Projection::Pinhole(pinhole)) => {
+            blobs.push(&pinhole.blob.data);
+            blobs.extend(&pinhole.mask);
+        }
+        Some(Projection::Spherical(spherical)) => {
+            blobs.push(&spherical.blob.data);
+            blobs.extend(&spherical.mask);
+        }
+        Some(Projection::Cylindrical(cylindrical)) => {
+            blobs.push(&cylindrical.blob.data);
+            blobs.extend(&cylindrical.mask);
+        }
+        None => {}
+    }
+    if let Some(reference) = &image.visual_reference {
+        blobs.push(&reference.blob.data);
+        blobs.extend(&reference.mask);
+    }
+    blobs
+}
+
+/// Computes the CRC-32C (Castagnoli) checksum used by the E57 pages.
+fn crc32c(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0_u32; 256];
+        let mut index = 0;
+        while index < 256 {
+            let mut value = index as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                value = if value & 1 == 0 {
+                    value >> 1
+                } else {
+                    (value >> 1) ^ 0x82F6_3B78
+                };
+                bit += 1;
+            }
+            table[index] = value;
+            index += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0_u32, |crc, &byte| {
+        TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{cartesian_prototype, write_e57};
+    use e57::{Record, RecordValue};
+
+    /// Writes an E57 file whose point data spans several pages.
+    fn write_large_e57(path: &Path) {
+        let points = (0..500)
+            .map(|value| vec![RecordValue::Double(f64::from(value)); 3])
+            .collect();
+        write_e57(path, cartesian_prototype(), points);
+    }
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+    }
+
+    #[test]
+    fn test_validate_valid_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("valid.e57");
+        write_large_e57(&path);
+
+        let issues = validate_file(&path).expect("Failed to validate E57 file");
+        assert_eq!(issues, []);
+    }
+
+    #[test]
+    fn test_validate_corrupted_page() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("corrupted.e57");
+        write_large_e57(&path);
+
+        let mut data = std::fs::read(&path).expect("Failed to read E57 file");
+        data[1024 + 100] ^= 0xFF;
+        std::fs::write(&path, data).expect("Failed to write E57 file");
+
+        let issues = validate_file(&path).expect("Failed to validate E57 file");
+        assert_eq!(issues[0].offset, Some(1024));
+        assert!(issues[0].message.starts_with("Checksum mismatch in page 1"));
+        // The point data of the scan lies in the corrupted page.
+        assert!(
+            issues
+                .iter()
+                .any(|issue| issue.scan == Some(0) && issue.offset.is_some())
+        );
+    }
+
+    #[test]
+    fn test_validate_truncated_file() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("truncated.e57");
+        std::fs::write(&path, b"ASTM-E57").expect("Failed to write E57 file");
+
+        let issues = validate_file(&path).expect("Failed to validate E57 file");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].offset, Some(0));
+    }
+
+    #[test]
+    fn test_check_prototype() {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.prototype = vec![
+            Record {
+                name: RecordName::CartesianX,
+                data_type: RecordDataType::F64,
+            },
+            Record {
+                name: RecordName::CartesianY,
+                data_type: RecordDataType::F64,
+            },
+            Record {
+                name: RecordName::Intensity,
+                data_type: RecordDataType::Integer { min: 10, max: 0 },
+            },
+        ];
+
+        let issues = check_prototype(&pointcloud);
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].starts_with("Invalid data type"));
+        assert_eq!(
+            issues[1],
+            "Incomplete cartesian coordinates in the prototype"
+        );
+    }
+}